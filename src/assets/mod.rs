@@ -2,9 +2,18 @@
 //!
 //! Handles copying and fingerprinting of static assets.
 
-use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha384};
+use walkdir::WalkDir;
+
+use crate::cache::write_if_changed;
+use crate::config::AssetsConfig;
 
 /// An asset with its content hash for cache busting.
 #[derive(Debug, Clone)]
@@ -95,6 +104,289 @@ pub fn process_asset(source: &Path, content: &[u8]) -> HashedAsset {
     }
 }
 
+/// The asset manifest mapping logical asset paths to output files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetManifest {
+    /// Schema version URL
+    #[serde(rename = "$schema")]
+    pub schema: String,
+
+    /// Asset manifest format version
+    pub version: String,
+
+    /// Map of logical paths (e.g., `images/logo.png`) to asset entries
+    pub assets: BTreeMap<String, AssetEntry>,
+}
+
+/// Metadata for a single copied asset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetEntry {
+    /// Output path relative to the output directory
+    pub path: PathBuf,
+
+    /// URL path the asset is served from
+    pub url: String,
+
+    /// SRI integrity hash (if enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+}
+
+impl AssetManifest {
+    /// Create a new empty asset manifest.
+    pub fn new() -> Self {
+        Self {
+            schema: "https://schemas.arusty.dev/mdbook-htmx/assets-manifest.schema.json"
+                .to_string(),
+            version: "1.0.0".to_string(),
+            assets: BTreeMap::new(),
+        }
+    }
+
+    /// Look up an asset by its logical path.
+    pub fn get(&self, logical: &str) -> Option<&AssetEntry> {
+        self.assets.get(logical.trim_start_matches('/'))
+    }
+
+    /// Look up an asset by its logical file path.
+    pub fn get_path(&self, logical: &Path) -> Option<&AssetEntry> {
+        self.assets.get(&logical_key(logical))
+    }
+
+    /// Serialize the asset manifest to JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl Default for AssetManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Copies assets into the output `assets/` directory.
+///
+/// Assets are fingerprinted when `hash-assets` is enabled and get an SRI
+/// hash when `sri-enabled` is set. Every copied file is recorded in the
+/// [`AssetManifest`].
+///
+/// The first asset added under a logical path wins; a different file added
+/// under the same path later is skipped with a warning.
+pub struct AssetPipeline<'a> {
+    config: &'a AssetsConfig,
+    assets_dir: PathBuf,
+    base_url: String,
+    manifest: AssetManifest,
+    /// Source file of each copied asset (None for generated content)
+    sources: BTreeMap<String, Option<PathBuf>>,
+}
+
+impl<'a> AssetPipeline<'a> {
+    /// Create a pipeline writing into `{output_dir}/assets`.
+    ///
+    /// # Arguments
+    /// * `config` - Asset handling settings
+    /// * `output_dir` - Build output directory
+    /// * `base_url` - URL prefix the site is served under, ending in `/`
+    pub fn new(config: &'a AssetsConfig, output_dir: &Path, base_url: &str) -> Self {
+        Self {
+            config,
+            assets_dir: output_dir.join("assets"),
+            base_url: base_url.to_string(),
+            manifest: AssetManifest::new(),
+            sources: BTreeMap::new(),
+        }
+    }
+
+    /// Copy a single file into the assets directory.
+    ///
+    /// # Arguments
+    /// * `logical` - Path relative to the assets root (e.g., `images/logo.png`)
+    /// * `source` - File to copy
+    ///
+    /// # Returns
+    /// The manifest entry for the copied asset
+    pub fn add_file(&mut self, logical: &Path, source: &Path) -> Result<&AssetEntry> {
+        let key = logical_key(logical);
+
+        match self.sources.get(&key) {
+            None => {
+                let content = fs::read(source)
+                    .with_context(|| format!("Failed to read asset: {}", source.display()))?;
+                self.add_bytes(logical, &content)?;
+                self.sources.insert(key.clone(), Some(source.to_path_buf()));
+                debug!("Copied asset {}", source.display());
+            }
+            Some(Some(first)) if first == source => {}
+            Some(first) => warn!(
+                "Asset {} from {} is already provided by {}; keeping the first",
+                key,
+                source.display(),
+                first
+                    .as_deref()
+                    .map_or("generated content".into(), Path::to_string_lossy)
+            ),
+        }
+
+        Ok(&self.manifest.assets[&key])
+    }
+
+    /// Write generated content into the assets directory.
+    ///
+    /// Does nothing if an asset with the same logical path was already added.
+    ///
+    /// # Arguments
+    /// * `logical` - Path relative to the assets root (e.g., `highlight.css`)
+    /// * `content` - File contents
+    ///
+    /// # Returns
+    /// The manifest entry for the asset
+    pub fn add_bytes(&mut self, logical: &Path, content: &[u8]) -> Result<&AssetEntry> {
+        let key = logical_key(logical);
+
+        if !self.manifest.assets.contains_key(&key) {
+            let processed = process_asset(logical, content);
+            let relative = if self.config.hash_assets {
+                processed.output
+            } else {
+                logical.to_path_buf()
+            };
+
+            let output = self.assets_dir.join(&relative);
+            write_if_changed(&output, content)
+                .with_context(|| format!("Failed to write asset: {}", output.display()))?;
+            debug!("Wrote asset {}", output.display());
+
+            let relative = logical_key(&relative);
+            self.sources.insert(key.clone(), None);
+            self.manifest.assets.insert(
+                key.clone(),
+                AssetEntry {
+                    path: PathBuf::from("assets").join(&relative),
+                    url: format!("{}assets/{}", self.base_url, relative),
+                    integrity: self.config.sri_enabled.then_some(processed.integrity),
+                },
+            );
+        }
+
+        Ok(&self.manifest.assets[&key])
+    }
+
+    /// Copy every file in a directory, keeping its layout under `assets/`.
+    pub fn add_dir(&mut self, dir: &Path) -> Result<()> {
+        self.add_dir_filtered(dir, |_| true)
+    }
+
+    /// Copy the files in a directory accepted by `include`.
+    pub fn add_dir_filtered<F>(&mut self, dir: &Path, include: F) -> Result<()>
+    where
+        F: Fn(&Path) -> bool,
+    {
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry =
+                entry.with_context(|| format!("Failed to read assets: {}", dir.display()))?;
+            if !entry.file_type().is_file() || !include(entry.path()) {
+                continue;
+            }
+
+            let logical = entry.path().strip_prefix(dir)?.to_path_buf();
+            self.add_file(&logical, entry.path())?;
+        }
+
+        Ok(())
+    }
+
+    /// Finish processing and return the asset manifest.
+    ///
+    /// Fingerprinted files left in `assets/` by earlier builds that are not
+    /// in the manifest are removed.
+    pub fn finish(self) -> Result<AssetManifest> {
+        if self.assets_dir.is_dir() {
+            let current: Vec<_> = self
+                .manifest
+                .assets
+                .values()
+                .map(|entry| entry.path.strip_prefix("assets").unwrap_or(&entry.path))
+                .collect();
+
+            for entry in WalkDir::new(&self.assets_dir) {
+                let entry = entry.with_context(|| {
+                    format!("Failed to read assets: {}", self.assets_dir.display())
+                })?;
+                let relative = entry.path().strip_prefix(&self.assets_dir)?;
+                if entry.file_type().is_file()
+                    && is_fingerprinted(relative)
+                    && !current.contains(&relative)
+                {
+                    debug!("Removing stale asset {}", entry.path().display());
+                    fs::remove_file(entry.path())
+                        .with_context(|| format!("Failed to remove {}", entry.path().display()))?;
+                }
+            }
+        }
+
+        Ok(self.manifest)
+    }
+}
+
+/// Whether a file name carries a content hash added by [`hashed_path`].
+fn is_fingerprinted(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let parts: Vec<_> = name.split('.').skip(1).collect();
+    parts.iter().rev().take(2).any(|part| {
+        part.len() == 8
+            && part
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, 'a'..='f'))
+    })
+}
+
+/// Convert a logical path to a manifest key with `/` separators.
+fn logical_key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Resolve a relative asset URL from a chapter against the source directory.
+///
+/// Returns the path relative to the source directory, or None for URLs that
+/// are absolute, external, or escape the source directory.
+///
+/// # Arguments
+/// * `chapter_path` - Chapter path relative to the source directory
+/// * `url` - The URL as written in the Markdown (e.g., `../images/logo.png`)
+pub fn resolve_relative_url(chapter_path: &Path, url: &str) -> Option<PathBuf> {
+    if url.is_empty() || url.starts_with('/') || url.starts_with('#') || url.contains("://") {
+        return None;
+    }
+    if url.starts_with("data:") || url.starts_with("mailto:") {
+        return None;
+    }
+
+    // Drop query strings and fragments
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+
+    let base = chapter_path.parent().unwrap_or(Path::new(""));
+    let mut resolved = PathBuf::new();
+    for component in base.join(url).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(resolved)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,4 +411,109 @@ mod tests {
         let integrity = compute_integrity(content);
         assert!(integrity.starts_with("sha384-"));
     }
+
+    #[test]
+    fn test_resolve_relative_url() {
+        let chapter = Path::new("guide/install.md");
+        assert_eq!(
+            resolve_relative_url(chapter, "img/shot.png"),
+            Some(PathBuf::from("guide/img/shot.png"))
+        );
+        assert_eq!(
+            resolve_relative_url(chapter, "../images/logo.svg?v=2"),
+            Some(PathBuf::from("images/logo.svg"))
+        );
+        assert_eq!(resolve_relative_url(chapter, "../../escape.png"), None);
+        assert_eq!(resolve_relative_url(chapter, "/abs.png"), None);
+        assert_eq!(resolve_relative_url(chapter, "https://x.dev/a.png"), None);
+    }
+
+    #[test]
+    fn test_pipeline_hashes_and_records_assets() {
+        let src = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        fs::create_dir_all(src.path().join("css")).unwrap();
+        fs::write(src.path().join("css/theme.css"), "body {}").unwrap();
+
+        let config = AssetsConfig::default();
+        let mut pipeline = AssetPipeline::new(&config, out.path(), "/");
+        pipeline.add_dir(src.path()).unwrap();
+        let manifest = pipeline.finish().unwrap();
+
+        let entry = manifest.get("css/theme.css").unwrap();
+        let hash = compute_short_hash(b"body {}");
+        assert_eq!(entry.url, format!("/assets/css/theme.{}.css", hash));
+        assert!(out.path().join(&entry.path).is_file());
+        assert!(entry.integrity.as_deref().unwrap().starts_with("sha384-"));
+    }
+
+    #[test]
+    fn test_pipeline_without_hashing() {
+        let src = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("logo.png"), b"png").unwrap();
+
+        let config = AssetsConfig {
+            hash_assets: false,
+            sri_enabled: false,
+            additional_assets: None,
+        };
+        let mut pipeline = AssetPipeline::new(&config, out.path(), "/");
+        let entry = pipeline
+            .add_file(Path::new("logo.png"), &src.path().join("logo.png"))
+            .unwrap();
+
+        assert_eq!(entry.url, "/assets/logo.png");
+        assert!(entry.integrity.is_none());
+        assert!(out.path().join("assets/logo.png").is_file());
+    }
+
+    #[test]
+    fn test_pipeline_keeps_first_of_colliding_assets() {
+        let src = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("first.png"), b"first").unwrap();
+        fs::write(src.path().join("second.png"), b"second").unwrap();
+
+        let config = AssetsConfig::default();
+        let mut pipeline = AssetPipeline::new(&config, out.path(), "/");
+        let logical = Path::new("images/logo.png");
+        let first = pipeline
+            .add_file(logical, &src.path().join("first.png"))
+            .unwrap()
+            .clone();
+        let second = pipeline
+            .add_file(logical, &src.path().join("second.png"))
+            .unwrap();
+
+        assert_eq!(second.url, first.url);
+        assert_eq!(fs::read(out.path().join(&second.path)).unwrap(), b"first");
+    }
+
+    #[test]
+    fn test_pipeline_removes_stale_fingerprinted_files() {
+        let out = tempfile::tempdir().unwrap();
+        let config = AssetsConfig::default();
+
+        let mut pipeline = AssetPipeline::new(&config, out.path(), "/");
+        let old = pipeline
+            .add_bytes(Path::new("css/theme.css"), b"old")
+            .unwrap()
+            .path
+            .clone();
+        pipeline.finish().unwrap();
+        fs::write(out.path().join("assets/notes.txt"), "kept").unwrap();
+
+        let mut pipeline = AssetPipeline::new(&config, out.path(), "/");
+        let new = pipeline
+            .add_bytes(Path::new("css/theme.css"), b"new")
+            .unwrap()
+            .path
+            .clone();
+        pipeline.finish().unwrap();
+
+        assert!(!out.path().join(&old).exists());
+        assert!(out.path().join(&new).is_file());
+        assert!(out.path().join("assets/notes.txt").is_file());
+    }
 }
@@ -0,0 +1,297 @@
+//! Canonical book data model.
+//!
+//! Generates book.json, the canonical representation of the book per
+//! ADR-0002. HTML pages, fragments, the manifest and the search index are
+//! all derived from the same data.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::{BookItem, Chapter};
+use crate::frontmatter::Frontmatter;
+
+/// The canonical book data written to book.json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookData {
+    /// Schema version URL
+    #[serde(rename = "$schema")]
+    pub schema: String,
+
+    /// Book data format version
+    pub version: String,
+
+    /// Book title
+    pub title: Option<String>,
+
+    /// Book description
+    pub description: Option<String>,
+
+    /// Book language code
+    pub language: String,
+
+    /// Book authors
+    pub authors: Vec<String>,
+
+    /// Chapter tree in SUMMARY.md order
+    pub items: Vec<BookDataItem>,
+}
+
+/// A single entry in the chapter tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BookDataItem {
+    /// A chapter with rendered content
+    Chapter(Box<ChapterData>),
+    /// A separator line in the TOC
+    Separator,
+    /// A part title (header without content)
+    PartTitle {
+        /// Part title text
+        title: String,
+    },
+}
+
+/// Rendered data for a single chapter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterData {
+    /// Chapter name from SUMMARY.md
+    pub name: String,
+
+    /// Display title (frontmatter title or chapter name)
+    pub title: String,
+
+    /// URL path (None for draft chapters)
+    pub path: Option<String>,
+
+    /// Source file path relative to src/
+    pub source: Option<PathBuf>,
+
+    /// Chapter number (e.g., "1.2")
+    pub number: Option<String>,
+
+    /// Whether this is a draft chapter (no source file)
+    pub draft: bool,
+
+    /// Parsed frontmatter
+    pub frontmatter: Frontmatter,
+
+    /// Rendered HTML content
+    pub content: String,
+
+    /// Headings with anchors
+    pub headings: Vec<HeadingData>,
+
+    /// Content hash for cache invalidation
+    pub content_hash: Option<String>,
+
+    /// Nested chapters
+    pub children: Vec<BookDataItem>,
+}
+
+/// A heading within a chapter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadingData {
+    /// Heading level (1-6)
+    pub level: u8,
+
+    /// Heading text
+    pub text: String,
+
+    /// Anchor ID (without leading `#`)
+    pub id: String,
+}
+
+/// Rendered output for a chapter, used to populate [`ChapterData`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChapterContent {
+    /// Display title
+    pub title: String,
+    /// URL path
+    pub path: String,
+    /// Parsed frontmatter
+    pub frontmatter: Frontmatter,
+    /// Rendered HTML content
+    pub content: String,
+    /// Headings as (level, text, anchor) tuples
+    pub headings: Vec<(u8, String, String)>,
+    /// Content hash
+    pub content_hash: String,
+}
+
+impl BookData {
+    /// Create a new book with no chapters.
+    pub fn new(
+        title: Option<String>,
+        description: Option<String>,
+        language: String,
+        authors: Vec<String>,
+    ) -> Self {
+        Self {
+            schema: "https://schemas.arusty.dev/mdbook-htmx/book.schema.json".to_string(),
+            version: "1.0.0".to_string(),
+            title,
+            description,
+            language,
+            authors,
+            items: Vec::new(),
+        }
+    }
+
+    /// Build the chapter tree from the book sections.
+    ///
+    /// # Arguments
+    /// * `sections` - Top-level book items from the RenderContext
+    /// * `lookup` - Returns the rendered content for a chapter, if it was rendered
+    pub fn build_items<'a, F>(sections: &'a [BookItem], lookup: &F) -> Vec<BookDataItem>
+    where
+        F: Fn(&'a Chapter) -> Option<ChapterContent>,
+    {
+        sections
+            .iter()
+            .map(|item| match item {
+                BookItem::Chapter(chapter) => BookDataItem::Chapter(Box::new(ChapterData::new(
+                    chapter,
+                    lookup(chapter),
+                    lookup,
+                ))),
+                BookItem::Separator => BookDataItem::Separator,
+                BookItem::PartTitle(title) => BookDataItem::PartTitle {
+                    title: title.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Serialize the book to JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl ChapterData {
+    fn new<'a, F>(chapter: &'a Chapter, rendered: Option<ChapterContent>, lookup: &F) -> Self
+    where
+        F: Fn(&'a Chapter) -> Option<ChapterContent>,
+    {
+        let number = chapter.number.as_ref().map(|nums| {
+            nums.iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        });
+        let children = BookData::build_items(&chapter.sub_items, lookup);
+        let draft = chapter.path.is_none();
+
+        match rendered {
+            Some(rendered) => Self {
+                name: chapter.name.clone(),
+                title: rendered.title,
+                path: Some(rendered.path),
+                source: chapter.source_path.clone().or_else(|| chapter.path.clone()),
+                number,
+                draft,
+                frontmatter: rendered.frontmatter,
+                content: rendered.content,
+                headings: rendered
+                    .headings
+                    .into_iter()
+                    .map(|(level, text, id)| HeadingData { level, text, id })
+                    .collect(),
+                content_hash: Some(rendered.content_hash),
+                children,
+            },
+            None => Self {
+                name: chapter.name.clone(),
+                title: chapter.name.clone(),
+                path: None,
+                source: chapter.source_path.clone(),
+                number,
+                draft,
+                frontmatter: Frontmatter::default(),
+                content: String::new(),
+                headings: Vec::new(),
+                content_hash: None,
+                children,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(name: &str, path: Option<&str>, sub_items: Vec<BookItem>) -> Chapter {
+        Chapter {
+            name: name.to_string(),
+            content: String::new(),
+            path: path.map(PathBuf::from),
+            source_path: path.map(PathBuf::from),
+            number: Some(vec![1]),
+            sub_items,
+            parent_names: vec![],
+        }
+    }
+
+    fn render(ch: &Chapter) -> Option<ChapterContent> {
+        ch.path.as_ref().map(|p| ChapterContent {
+            title: ch.name.clone(),
+            path: format!("/{}", p.with_extension("").display()),
+            content: "<p>Hello</p>".to_string(),
+            headings: vec![(1, "Hello".to_string(), "hello".to_string())],
+            content_hash: "abcd1234".to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_build_items_preserves_structure() {
+        let sections = vec![
+            BookItem::PartTitle("Guide".to_string()),
+            BookItem::Chapter(chapter(
+                "Intro",
+                Some("intro.md"),
+                vec![BookItem::Chapter(chapter(
+                    "Nested",
+                    Some("intro/nested.md"),
+                    vec![],
+                ))],
+            )),
+            BookItem::Separator,
+            BookItem::Chapter(chapter("Draft", None, vec![])),
+        ];
+
+        let items = BookData::build_items(&sections, &render);
+        assert_eq!(items.len(), 4);
+        assert!(matches!(&items[0], BookDataItem::PartTitle { title } if title == "Guide"));
+        assert!(matches!(items[2], BookDataItem::Separator));
+
+        let BookDataItem::Chapter(intro) = &items[1] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(intro.path.as_deref(), Some("/intro"));
+        assert_eq!(intro.number.as_deref(), Some("1"));
+        assert_eq!(intro.headings[0].id, "hello");
+        assert_eq!(intro.children.len(), 1);
+
+        let BookDataItem::Chapter(draft) = &items[3] else {
+            panic!("expected chapter");
+        };
+        assert!(draft.draft);
+        assert!(draft.path.is_none());
+        assert!(draft.content.is_empty());
+    }
+
+    #[test]
+    fn test_book_data_serialization() {
+        let mut book = BookData::new(Some("Test".to_string()), None, "en".to_string(), vec![]);
+        book.items = vec![BookDataItem::PartTitle {
+            title: "Part".to_string(),
+        }];
+
+        let json = book.to_json().unwrap();
+        assert!(json.contains("book.schema.json"));
+        assert!(json.contains("\"type\": \"part_title\""));
+    }
+}
@@ -0,0 +1,185 @@
+//! Incremental build cache.
+//!
+//! Records each chapter's input hash in `.htmx-cache.json` in the output
+//! directory, along with the manifest, search, book.json and link check data
+//! produced for it. A chapter whose hash is unchanged is not re-rendered;
+//! its cached data is merged as if it had been.
+//!
+//! A chapter's input hash covers its source (including frontmatter) and the
+//! book-wide context hash: config, crate version, builtin and theme
+//! templates, the chapter tree used for navigation, and the asset manifest.
+//! Changing any of those re-renders every chapter.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::book::ChapterContent;
+use crate::links::Link;
+use crate::manifest::PageEntry;
+use crate::search::SearchDocument;
+
+/// Cache file name, relative to the output directory.
+pub const CACHE_FILE: &str = ".htmx-cache.json";
+
+/// The build cache written after each build.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildCache {
+    /// Cache format version
+    pub version: String,
+
+    /// Cached chapters keyed by source path
+    pub chapters: BTreeMap<PathBuf, ChapterRecord>,
+}
+
+/// Everything a chapter contributes to the book-wide outputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterRecord {
+    /// Hash of the chapter's inputs
+    pub input_hash: String,
+
+    /// Manifest key
+    pub url_path: String,
+
+    /// Manifest entry
+    pub entry: PageEntry,
+
+    /// Search document (None if excluded or search is disabled)
+    pub search_doc: Option<SearchDocument>,
+
+    /// Data for book.json (None if disabled)
+    pub book: Option<ChapterContent>,
+
+    /// Links to check (empty if link checking is disabled)
+    pub links: Vec<Link>,
+
+    /// Heading anchors of the page
+    pub anchors: Vec<String>,
+}
+
+impl BuildCache {
+    /// Current cache format version.
+    const VERSION: &'static str = "3";
+
+    /// Load the cache from `output_dir`.
+    ///
+    /// Returns an empty cache if the file is missing, unreadable or from a
+    /// different format version.
+    pub fn load(output_dir: &Path) -> Self {
+        let path = output_dir.join(CACHE_FILE);
+        let Ok(json) = fs::read_to_string(&path) else {
+            return Self::new();
+        };
+
+        match serde_json::from_str::<Self>(&json) {
+            Ok(cache) if cache.version == Self::VERSION => cache,
+            Ok(_) => {
+                debug!("Ignoring build cache from another version");
+                Self::new()
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable build cache {}: {}", path.display(), e);
+                Self::new()
+            }
+        }
+    }
+
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION.to_string(),
+            chapters: BTreeMap::new(),
+        }
+    }
+
+    /// Look up a chapter whose inputs still hash to `input_hash`.
+    pub fn get(&self, source: &Path, input_hash: &str) -> Option<&ChapterRecord> {
+        self.chapters
+            .get(source)
+            .filter(|record| record.input_hash == input_hash)
+    }
+
+    /// Write the cache to `output_dir`.
+    pub fn save(&self, output_dir: &Path) -> io::Result<()> {
+        let json = serde_json::to_string(self)?;
+        write_if_changed(&output_dir.join(CACHE_FILE), json.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Hash a sequence of inputs into a cache key.
+///
+/// Parts are length-prefixed so `["ab", "c"]` and `["a", "bc"]` differ.
+pub fn hash_inputs<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> String {
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    for part in parts {
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    format!("{:032x}", hasher.digest128())
+}
+
+/// Write `contents` to `path` unless the file already holds exactly that.
+///
+/// Leaving unchanged files alone keeps their modification times, so sync
+/// tools only upload what changed. Returns whether the file was written.
+pub fn write_if_changed(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    if let Ok(existing) = fs::metadata(path) {
+        if existing.len() == contents.len() as u64 && fs::read(path)? == contents {
+            return Ok(false);
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_inputs() {
+        let a = hash_inputs([b"ab".as_slice(), b"c".as_slice()]);
+        let b = hash_inputs([b"a".as_slice(), b"bc".as_slice()]);
+        assert_ne!(a, b);
+        assert_eq!(a, hash_inputs([b"ab".as_slice(), b"c".as_slice()]));
+    }
+
+    #[test]
+    fn test_write_if_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/file.html");
+
+        assert!(write_if_changed(&path, b"one").unwrap());
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        assert!(!write_if_changed(&path, b"one").unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        assert!(write_if_changed(&path, b"two").unwrap());
+        assert_eq!(fs::read(&path).unwrap(), b"two");
+    }
+
+    #[test]
+    fn test_load_ignores_other_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(CACHE_FILE),
+            r#"{"version":"0","chapters":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(BuildCache::load(dir.path()).version, BuildCache::VERSION);
+
+        fs::write(dir.path().join(CACHE_FILE), "not json").unwrap();
+        assert!(BuildCache::load(dir.path()).chapters.is_empty());
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! Handles parsing of [output.htmx] configuration from book.toml.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::BuildError;
@@ -40,6 +42,18 @@ pub struct HtmxConfig {
     #[serde(default)]
     pub output_mode: OutputMode,
 
+    /// Write the canonical book.json (default: true)
+    #[serde(default = "defaults::book_json")]
+    pub book_json: bool,
+
+    /// Skip re-rendering unchanged chapters using the build cache (default: true)
+    #[serde(default = "defaults::incremental")]
+    pub incremental: bool,
+
+    /// Write `generated_at` to manifest.json (default: true)
+    #[serde(default = "defaults::timestamps")]
+    pub timestamps: bool,
+
     /// Navigation settings
     #[serde(default)]
     pub navigation: NavigationConfig,
@@ -52,12 +66,32 @@ pub struct HtmxConfig {
     #[serde(default)]
     pub assets: AssetsConfig,
 
+    /// Syntax highlighting settings
+    #[serde(default)]
+    pub highlight: HighlightConfig,
+
+    /// Broken link checking
+    #[serde(default)]
+    pub link_check: LinkCheckConfig,
+
+    /// TeX math settings
+    #[serde(default)]
+    pub math: MathConfig,
+
     /// Default scope for unscoped content
     pub default_scope: Option<String>,
 
+    /// Audience scopes to publish as separate output trees under `scopes/`
+    #[serde(default)]
+    pub scopes: BTreeMap<String, ScopeConfig>,
+
     /// Custom theme directory path
     pub theme_dir: Option<String>,
 
+    /// URL path the site is served under (default: /)
+    #[serde(default = "defaults::base_path")]
+    pub base_path: String,
+
     /// Authentication configuration
     #[serde(default)]
     pub authn: AuthnConfig,
@@ -118,10 +152,14 @@ pub struct NavigationConfig {
     pub breadcrumbs: bool,
     /// Show table of contents
     pub toc: bool,
+    /// Deepest heading level listed in the table of contents (2-6)
+    pub toc_depth: u8,
     /// Show previous/next navigation
     pub prev_next: bool,
     /// Collapsible sidebar sections
     pub collapsible_sidebar: bool,
+    /// Show a `#` permalink anchor next to each heading
+    pub permalinks: bool,
 }
 
 impl Default for NavigationConfig {
@@ -129,8 +167,10 @@ impl Default for NavigationConfig {
         Self {
             breadcrumbs: true,
             toc: true,
+            toc_depth: 3,
             prev_next: true,
             collapsible_sidebar: true,
+            permalinks: false,
         }
     }
 }
@@ -151,6 +191,11 @@ pub struct SearchConfig {
     pub max_excerpt_length: Option<usize>,
     /// Include auth metadata in search index for filtering
     pub include_auth: bool,
+    /// Also write search.db, a SQLite FTS5 database (requires the
+    /// `sqlite-search` feature)
+    pub sqlite: bool,
+    /// Write pre-filtered index shards per auth level and role
+    pub shards: bool,
 }
 
 impl Default for SearchConfig {
@@ -162,10 +207,80 @@ impl Default for SearchConfig {
             heading_split_level: 3,
             max_excerpt_length: None,
             include_auth: true,
+            sqlite: false,
+            shards: true,
+        }
+    }
+}
+
+/// Syntax highlighting configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HighlightConfig {
+    /// Highlight fenced code blocks at build time (default: off)
+    pub enabled: bool,
+    /// Theme used for `assets/highlight.css`
+    pub theme: String,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            theme: "InspiredGitHub".to_string(),
         }
     }
 }
 
+/// Broken link checking configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LinkCheckConfig {
+    /// Check links to chapters and anchors after rendering
+    pub enabled: bool,
+    /// What to do when broken links are found
+    pub mode: LinkCheckMode,
+}
+
+impl Default for LinkCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: LinkCheckMode::default(),
+        }
+    }
+}
+
+/// TeX math configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MathConfig {
+    /// Convert `$...$` and `$$...$$` to MathML at build time (default: off)
+    pub enabled: bool,
+}
+
+/// Outcome of a build with broken links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkCheckMode {
+    /// Log a warning per broken link (default)
+    #[default]
+    Warn,
+    /// Report the broken links and fail the build
+    Fail,
+}
+
+/// A published audience scope.
+///
+/// Its output tree holds pages with this scope, pages with an included
+/// scope, and unscoped pages.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ScopeConfig {
+    /// Other scopes whose pages are also published in this scope
+    pub includes: Vec<String>,
+}
+
 /// Asset handling configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
@@ -281,11 +396,19 @@ impl Default for HtmxConfig {
             target: defaults::target(),
             push_url: defaults::push_url(),
             output_mode: OutputMode::default(),
+            book_json: defaults::book_json(),
+            incremental: defaults::incremental(),
+            timestamps: defaults::timestamps(),
             navigation: NavigationConfig::default(),
             search: SearchConfig::default(),
             assets: AssetsConfig::default(),
+            highlight: HighlightConfig::default(),
+            link_check: LinkCheckConfig::default(),
+            math: MathConfig::default(),
             default_scope: None,
+            scopes: BTreeMap::new(),
             theme_dir: None,
+            base_path: defaults::base_path(),
             authn: AuthnConfig::default(),
             authz: AuthzConfig::default(),
         }
@@ -318,6 +441,22 @@ mod defaults {
     pub fn push_url() -> bool {
         true
     }
+
+    pub fn book_json() -> bool {
+        true
+    }
+
+    pub fn incremental() -> bool {
+        true
+    }
+
+    pub fn timestamps() -> bool {
+        true
+    }
+
+    pub fn base_path() -> String {
+        "/".to_string()
+    }
 }
 
 impl HtmxConfig {
@@ -350,8 +489,51 @@ impl HtmxConfig {
             )));
         }
 
+        // Validate base path
+        if !self.base_path.starts_with('/') {
+            return Err(BuildError::ConfigError(format!(
+                "base-path must start with '/': {}",
+                self.base_path
+            )));
+        }
+
+        // The TOC lists H2 and below, so H1 or a level past H6 is a mistake
+        if !(2..=6).contains(&self.navigation.toc_depth) {
+            return Err(BuildError::ConfigError(format!(
+                "navigation.toc-depth must be between 2 and 6: {}",
+                self.navigation.toc_depth
+            )));
+        }
+
+        // Scope names become directory names under scopes/
+        for name in self.scopes.keys() {
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(BuildError::ConfigError(format!(
+                    "Invalid scope name '{}': use letters, digits, '-' and '_'",
+                    name
+                )));
+            }
+        }
+
+        // search.db needs rusqlite, which is optional
+        if self.search.sqlite && !cfg!(feature = "sqlite-search") {
+            return Err(BuildError::ConfigError(
+                "search.sqlite requires mdbook-htmx built with the sqlite-search feature"
+                    .to_string(),
+            ));
+        }
+
         Ok(())
     }
+
+    /// Base path normalized to end with `/` (e.g., `/docs/`).
+    pub fn base_url(&self) -> String {
+        format!("{}/", self.base_path.trim_end_matches('/'))
+    }
 }
 
 #[cfg(test)]
@@ -364,6 +546,69 @@ mod tests {
         assert!(config.boost);
         assert_eq!(config.target, "#content");
         assert_eq!(config.output_mode, OutputMode::Both);
+        assert!(config.book_json);
+        assert!(!config.highlight.enabled);
+    }
+
+    #[test]
+    fn test_toc_depth_validation() {
+        let mut config = HtmxConfig::default();
+        for (depth, valid) in [(0, false), (1, false), (2, true), (6, true), (7, false)] {
+            config.navigation.toc_depth = depth;
+            assert_eq!(config.validate().is_ok(), valid, "toc-depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn test_sqlite_search_requires_feature() {
+        let mut config = HtmxConfig::default();
+        config.search.sqlite = true;
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "sqlite-search"));
+    }
+
+    #[test]
+    fn test_base_path() {
+        let mut config = HtmxConfig::default();
+        assert_eq!(config.base_url(), "/");
+
+        config.base_path = "/docs".to_string();
+        assert_eq!(config.base_url(), "/docs/");
+        assert!(config.validate().is_ok());
+
+        config.base_path = "docs".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let value: toml::Value = toml::from_str(
+            r#"
+            [scopes]
+            public = {}
+            partner = { includes = ["public"] }
+            "#,
+        )
+        .unwrap();
+        let config = HtmxConfig::from_toml(Some(&value)).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.scopes.keys().collect::<Vec<_>>(),
+            ["partner", "public"]
+        );
+        assert_eq!(config.scopes["partner"].includes, ["public"]);
+
+        let mut config = HtmxConfig::default();
+        config
+            .scopes
+            .insert("../up".to_string(), ScopeConfig::default());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_math_config() {
+        assert!(!HtmxConfig::default().math.enabled);
+        let value: toml::Value = toml::from_str("[math]\nenabled = true").unwrap();
+        assert!(HtmxConfig::from_toml(Some(&value)).unwrap().math.enabled);
     }
 
     #[test]
@@ -33,6 +33,9 @@ pub struct Frontmatter {
     /// Custom template to use
     pub template: Option<String>,
 
+    /// Id of the heading where the lazily loaded extended part starts
+    pub extended_from: Option<String>,
+
     /// Exclude from search index
     #[serde(default)]
     pub no_search: bool,
@@ -3,30 +3,60 @@
 //! Provides the core rendering functionality for the mdbook-htmx backend.
 
 pub mod assets;
+pub mod book;
+pub mod cache;
 pub mod config;
 pub mod context;
 pub mod error;
 pub mod frontmatter;
+pub mod links;
 pub mod manifest;
 pub mod render;
+pub mod scope;
 pub mod search;
+pub mod serve;
 pub mod templates;
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
-use log::{debug, info};
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use rayon::prelude::*;
 use tera::Tera;
+use walkdir::WalkDir;
 
 pub use config::{HtmxConfig, OutputMode};
 pub use context::{Chapter, RenderContext};
 pub use error::BuildError;
 
+use crate::assets::{AssetManifest, AssetPipeline};
+use crate::book::{BookData, ChapterContent};
+use crate::cache::{BuildCache, ChapterRecord};
+use crate::config::{LinkCheckMode, ScopeConfig};
+use crate::context::BookItem;
 use crate::frontmatter::Frontmatter;
-use crate::manifest::{Manifest, PageEntry};
-use crate::render::oob::{render_oob_updates, NavItem, SidebarContext};
+use crate::links::LinkChecker;
+use crate::manifest::{Manifest, PageEntry, SearchShards};
+use crate::render::oob::{
+    build_breadcrumbs, build_nav_items, build_toc, render_oob_updates, render_toc_oob,
+    url_with_base, BreadcrumbContext, SidebarContext,
+};
+use crate::render::{Highlighter, MarkdownOptions};
+use crate::scope::ScopeFilter;
 use crate::search::{SearchDocumentBuilder, SearchIndex};
+use crate::serve::auth::{check_access, Access, User};
+use crate::templates::UrlContext;
+
+/// Logical asset path of the generated syntax highlighting stylesheet.
+const HIGHLIGHT_CSS: &str = "highlight.css";
+
+/// Template set used when a chapter does not set `template` in its frontmatter.
+const DEFAULT_TEMPLATE_SET: &str = "docs";
+
+/// Template for the lazily loaded extended part of a split chapter.
+const EXTENDED_TEMPLATE: &str = "partials/extended.html";
 
 /// The main renderer that processes MDBook content and produces HTMX-enhanced HTML.
 pub struct HtmxRenderer {
@@ -38,6 +68,24 @@ pub struct HtmxRenderer {
     tera: Tera,
     /// Output directory
     output_dir: PathBuf,
+    /// Audience scope this renderer publishes (None for the full book)
+    scope: Option<String>,
+}
+
+/// Book-wide state shared by every chapter render.
+struct RenderState {
+    /// Source paths of chapters hidden from navigation and search
+    hidden: HashSet<PathBuf>,
+    /// Copied assets, for rewriting image URLs
+    assets: AssetManifest,
+    /// Template engine with URL functions bound to the copied assets
+    tera: Tera,
+    /// Code block highlighter (None when highlighting is disabled)
+    highlighter: Option<Highlighter>,
+    /// Chapters from the previous build
+    cache: BuildCache,
+    /// Hash of the book-wide inputs every chapter depends on
+    context_hash: String,
 }
 
 /// Result of rendering a single chapter.
@@ -46,6 +94,12 @@ pub struct RenderedChapter {
     pub page: String,
     /// The content-only fragment (for HTMX)
     pub fragment: String,
+    /// The lazily loaded extended fragment, if the chapter is split
+    pub extended: Option<String>,
+    /// The rendered Markdown content (without layout), both parts
+    pub content: String,
+    /// Headings as (level, text, anchor) tuples
+    pub headings: Vec<(u8, String, String)>,
     /// Parsed frontmatter
     pub frontmatter: Frontmatter,
     /// Output path relative to output directory
@@ -79,8 +133,9 @@ impl HtmxRenderer {
 
         debug!("Loaded config: {:?}", config);
 
-        // Initialize template engine
-        let tera = templates::init_templates()?;
+        // Initialize template engine, with theme overrides relative to the book root
+        let theme_dir = config.theme_dir.as_ref().map(|dir| ctx.root.join(dir));
+        let tera = templates::init_templates(theme_dir.as_deref())?;
 
         // Determine output directory
         let output_dir = ctx.destination.clone();
@@ -90,6 +145,7 @@ impl HtmxRenderer {
             config,
             tera,
             output_dir,
+            scope: None,
         })
     }
 
@@ -100,7 +156,45 @@ impl HtmxRenderer {
     /// - `fragments/*.html` - Content-only fragments for HTMX
     /// - `manifest.json` - Page metadata for server integration
     /// - `search-index.json` - Search index for client/server-side search
+    /// - `search-index.*.json` - Search index shards per auth level and role
+    /// - `book.json` - Canonical book data (ADR-0002)
+    /// - `assets/` and `assets-manifest.json` - Copied, fingerprinted assets
+    /// - `scopes/<name>/` - The same outputs for each configured scope
     pub fn render(&self) -> Result<()> {
+        self.render_tree()?;
+
+        for (name, scope) in &self.config.scopes {
+            info!("Rendering scope '{}'", name);
+            self.for_scope(name, scope)?.render_tree()?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a renderer for one scope's output tree.
+    ///
+    /// The book is filtered before rendering, so chapters outside the scope
+    /// are neither written nor mentioned in navigation, manifest or search.
+    fn for_scope(&self, name: &str, scope: &ScopeConfig) -> Result<Self> {
+        let filter = ScopeFilter::new(name, scope, self.config.default_scope.as_deref());
+
+        let mut ctx = self.ctx.clone();
+        ctx.book.sections = filter.filter_items(&self.ctx.book.sections)?;
+
+        let mut config = self.config.clone();
+        config.scopes.clear();
+
+        Ok(Self {
+            ctx,
+            config,
+            tera: self.tera.clone(),
+            output_dir: self.output_dir.join(scope::SCOPES_DIR).join(name),
+            scope: Some(name.to_string()),
+        })
+    }
+
+    /// Render the book (or one scope of it) to `output_dir`.
+    fn render_tree(&self) -> Result<()> {
         info!("Rendering to {}", self.output_dir.display());
 
         // Create output directories
@@ -108,6 +202,10 @@ impl HtmxRenderer {
 
         // Build manifest as we render
         let mut manifest = Manifest::new();
+        if !self.config.timestamps {
+            manifest.generated_at = None;
+        }
+        manifest.scope = self.scope.clone();
 
         // Build search index if enabled
         let mut search_index = if self.config.search.enabled {
@@ -115,75 +213,98 @@ impl HtmxRenderer {
         } else {
             None
         };
-        let search_builder = SearchDocumentBuilder::new(&self.config.search);
+        let search_builder =
+            SearchDocumentBuilder::new(&self.config.search).with_math(self.config.math.enabled);
+
+        // Rendered chapter data for book.json, keyed by source path
+        let mut book_chapters: HashMap<PathBuf, ChapterContent> = HashMap::new();
 
         // Collect all chapters for prev/next navigation
         let chapters: Vec<_> = self.ctx.iter_chapters().collect();
         let chapter_count = chapters.len();
 
-        info!("Rendering {} chapters", chapter_count);
+        // Book-wide state: hidden chapters, highlighter and copied assets
+        let highlighter = if self.config.highlight.enabled {
+            Some(Highlighter::new(&self.config.highlight)?)
+        } else {
+            None
+        };
+        let assets = self.process_assets(&chapters, highlighter.as_ref())?;
+        let mut tera = self.tera.clone();
+        templates::register_functions(
+            &mut tera,
+            UrlContext {
+                assets: assets.clone(),
+                base_url: self.config.base_url(),
+            },
+        );
+        let hidden = self.collect_hidden(&chapters)?;
+        let context_hash = self.context_hash(&hidden, &assets)?;
+        let cache = if self.config.incremental {
+            BuildCache::load(&self.output_dir)
+        } else {
+            BuildCache::new()
+        };
+        let state = RenderState {
+            hidden,
+            assets,
+            tera,
+            highlighter,
+            cache,
+            context_hash,
+        };
 
-        // Render each chapter
-        for (idx, chapter) in chapters.iter().enumerate() {
-            // Skip chapters without a path (draft chapters)
-            let Some(ref path) = chapter.path else {
-                debug!("Skipping draft chapter: {}", chapter.name);
-                continue;
-            };
+        info!("Rendering {} chapters", chapter_count);
 
-            debug!("Rendering chapter: {} ({})", chapter.name, path.display());
+        // Render and write chapters in parallel; results come back in book order
+        let outputs = (0..chapter_count)
+            .into_par_iter()
+            .map(|idx| self.build_chapter(idx, &chapters, &state, &search_builder))
+            .collect::<Result<Vec<_>>>()?;
 
-            // Get prev/next chapters for navigation
-            let prev_chapter = if idx > 0 { chapters.get(idx - 1) } else { None };
-            let next_chapter = chapters.get(idx + 1);
+        // Merge in book order so output matches a serial build
+        let mut cache = BuildCache::new();
+        for (path, record) in outputs.into_iter().flatten() {
+            if let (Some(index), Some(doc)) = (search_index.as_mut(), &record.search_doc) {
+                index.add_document(doc.clone());
+            }
+            if let Some(content) = &record.book {
+                book_chapters.insert(path.clone(), content.clone());
+            }
+            manifest.add_page(record.url_path.clone(), record.entry.clone());
+            cache.chapters.insert(path, record);
+        }
 
-            // Render the chapter
-            let rendered = self.render_chapter(
-                chapter,
-                prev_chapter.copied(),
-                next_chapter.copied(),
-                &chapters,
-            )?;
+        if self.config.incremental {
+            self.remove_stale_outputs(&state.cache, &cache)?;
+            cache
+                .save(&self.output_dir)
+                .context("Failed to write build cache")?;
+        }
 
-            // Write output files
-            self.write_chapter(&rendered)?;
+        if self.config.link_check.enabled {
+            self.check_links(&cache)?;
+        }
 
-            // Add to manifest
-            let url_path = self.path_to_url(path);
-            manifest.add_page(
-                url_path.clone(),
-                PageEntry {
-                    title: rendered
-                        .frontmatter
-                        .title
-                        .clone()
-                        .unwrap_or_else(|| chapter.name.clone()),
-                    source: chapter.source_path.clone().unwrap_or_else(|| path.clone()),
-                    page_path: PathBuf::from("pages").join(&rendered.path),
-                    fragment_path: PathBuf::from("fragments").join(&rendered.path),
-                    scope: rendered.frontmatter.scope.clone(),
-                    authn: rendered.frontmatter.authn.as_ref().map(|a| a.to_string()),
-                    authz: rendered.frontmatter.authz.clone(),
-                    fallback: rendered.frontmatter.fallback.clone(),
-                    content_hash: assets::compute_short_hash(rendered.page.as_bytes()),
-                },
+        // Write book.json
+        if self.config.book_json {
+            let mut book = BookData::new(
+                self.ctx.config.book.title.clone(),
+                self.ctx.config.book.description.clone(),
+                self.ctx.config.book.language.clone(),
+                self.ctx.config.book.authors.clone(),
             );
+            book.items = BookData::build_items(&self.ctx.book.sections, &|ch: &Chapter| {
+                ch.path.as_ref().and_then(|p| book_chapters.get(p).cloned())
+            });
+            self.write_book_json(&book)?;
+        }
 
-            // Add to search index if enabled
-            if let Some(ref mut index) = search_index {
-                // Get content after frontmatter for indexing
-                let (_, content) = frontmatter::parse_frontmatter(&chapter.content, path)?;
-                let title = rendered
-                    .frontmatter
-                    .title
-                    .clone()
-                    .unwrap_or_else(|| chapter.name.clone());
-
-                if let Some(doc) =
-                    search_builder.build(url_path, title, content, &rendered.frontmatter)
-                {
-                    index.add_document(doc);
-                }
+        // Write search index and its shards, which the manifest lists
+        if let Some(index) = search_index {
+            self.write_search_index(&index)?;
+            if self.config.search.shards {
+                manifest.search = Some(self.write_search_shards(&index, &manifest)?);
             }
         }
 
@@ -192,15 +313,250 @@ impl HtmxRenderer {
             self.write_manifest(&manifest)?;
         }
 
-        // Write search index
-        if let Some(index) = search_index {
-            self.write_search_index(&index)?;
+        info!("Rendering complete");
+        Ok(())
+    }
+
+    /// Render a chapter, write its files and prepare its manifest, search
+    /// and book.json entries.
+    ///
+    /// Chapters whose inputs match the build cache are not re-rendered.
+    /// Returns `None` for draft chapters.
+    fn build_chapter(
+        &self,
+        idx: usize,
+        chapters: &[&Chapter],
+        state: &RenderState,
+        search_builder: &SearchDocumentBuilder,
+    ) -> Result<Option<(PathBuf, ChapterRecord)>> {
+        let chapter = chapters[idx];
+
+        // Skip chapters without a path (draft chapters)
+        let Some(ref path) = chapter.path else {
+            debug!("Skipping draft chapter: {}", chapter.name);
+            return Ok(None);
+        };
+
+        let input_hash = cache::hash_inputs([
+            state.context_hash.as_bytes(),
+            path.to_string_lossy().as_bytes(),
+            chapter.content.as_bytes(),
+        ]);
+        if let Some(record) = state.cache.get(path, &input_hash) {
+            if self.outputs_exist(&record.entry) {
+                debug!("Unchanged chapter: {} ({})", chapter.name, path.display());
+                return Ok(Some((path.clone(), record.clone())));
+            }
         }
 
-        info!("Rendering complete");
+        debug!("Rendering chapter: {} ({})", chapter.name, path.display());
+
+        // Get prev/next chapters for navigation, skipping hidden chapters
+        let hidden = &state.hidden;
+        let is_listed = |ch: &&&Chapter| !ch.path.as_ref().is_some_and(|p| hidden.contains(p));
+        let prev_chapter = chapters[..idx].iter().rev().find(is_listed).copied();
+        let next_chapter = chapters[idx + 1..].iter().find(is_listed).copied();
+
+        // Render the chapter
+        let rendered = self.render_chapter(chapter, prev_chapter, next_chapter, state)?;
+
+        // Write output files
+        self.write_chapter(&rendered)?;
+
+        // Manifest entry
+        let url_path = self.path_to_url(path);
+        let title = rendered
+            .frontmatter
+            .title
+            .clone()
+            .unwrap_or_else(|| chapter.name.clone());
+        let entry = PageEntry {
+            title: title.clone(),
+            source: chapter.source_path.clone().unwrap_or_else(|| path.clone()),
+            page_path: PathBuf::from("pages").join(&rendered.path),
+            fragment_path: PathBuf::from("fragments").join(&rendered.path),
+            extended_path: rendered
+                .extended
+                .is_some()
+                .then(|| PathBuf::from("fragments").join(extended_file(&rendered.path))),
+            scope: rendered.frontmatter.scope.clone(),
+            authn: rendered.frontmatter.authn.as_ref().map(|a| a.to_string()),
+            authz: rendered.frontmatter.authz.clone(),
+            fallback: rendered.frontmatter.fallback.clone(),
+            content_hash: assets::compute_short_hash(rendered.page.as_bytes()),
+            hidden: rendered.frontmatter.hidden,
+        };
+
+        // Content after frontmatter, for indexing and link checking
+        let (_, content) = frontmatter::parse_frontmatter(&chapter.content, path)?;
+
+        // Search document, if search is enabled
+        let search_doc = if self.config.search.enabled {
+            search_builder.build(
+                url_path.clone(),
+                title.clone(),
+                content,
+                &rendered.frontmatter,
+            )
+        } else {
+            None
+        };
+
+        // Links with their line numbers in the source file
+        let links = if self.config.link_check.enabled {
+            let frontmatter_len = chapter.content.len() - content.len();
+            let first_line = chapter.content[..frontmatter_len].matches('\n').count() + 1;
+            links::extract_links(content, first_line)
+        } else {
+            Vec::new()
+        };
+        let anchors = rendered
+            .headings
+            .iter()
+            .map(|(_, _, anchor)| anchor.clone())
+            .collect();
+
+        // Chapter data for book.json
+        let book = self.config.book_json.then(|| ChapterContent {
+            title,
+            path: url_path.clone(),
+            content_hash: assets::compute_short_hash(rendered.content.as_bytes()),
+            frontmatter: rendered.frontmatter,
+            content: rendered.content,
+            headings: rendered.headings,
+        });
+
+        Ok(Some((
+            path.clone(),
+            ChapterRecord {
+                input_hash,
+                url_path,
+                entry,
+                search_doc,
+                book,
+                links,
+                anchors,
+            },
+        )))
+    }
+
+    /// Check the links of every chapter against the pages written and their
+    /// heading anchors.
+    ///
+    /// Broken links are logged as warnings, or fail the build in `fail` mode.
+    fn check_links(&self, cache: &BuildCache) -> Result<()> {
+        let mut checker = LinkChecker::default();
+        for record in cache.chapters.values() {
+            checker.add_page(&record.url_path, record.anchors.iter().map(String::as_str));
+        }
+
+        let broken: Vec<_> = cache
+            .chapters
+            .iter()
+            .flat_map(|(path, record)| {
+                checker.check(path, &record.url_path, &record.entry.source, &record.links)
+            })
+            .collect();
+        if broken.is_empty() {
+            return Ok(());
+        }
+
+        let tree = match self.scope {
+            Some(ref scope) => format!(" in scope '{}'", scope),
+            None => String::new(),
+        };
+        let report: Vec<_> = broken.iter().map(ToString::to_string).collect();
+        let summary = format!(
+            "{} broken link(s){}:\n  {}",
+            broken.len(),
+            tree,
+            report.join("\n  ")
+        );
+        match self.config.link_check.mode {
+            LinkCheckMode::Warn => {
+                warn!("{}", summary);
+                Ok(())
+            }
+            LinkCheckMode::Fail => bail!(summary),
+        }
+    }
+
+    /// Whether the page (and fragment, if configured) of a cached chapter are
+    /// still in the output directory.
+    fn outputs_exist(&self, entry: &PageEntry) -> bool {
+        self.output_dir.join(&entry.page_path).is_file()
+            && (self.config.output_mode == OutputMode::Full
+                || self.output_dir.join(&entry.fragment_path).is_file())
+            && entry
+                .extended_path
+                .as_ref()
+                .is_none_or(|path| self.output_dir.join(path).is_file())
+    }
+
+    /// Remove pages and fragments of chapters that were in the previous
+    /// build but not this one (deleted, or moved out of this scope).
+    fn remove_stale_outputs(&self, previous: &BuildCache, current: &BuildCache) -> Result<()> {
+        for (source, record) in &previous.chapters {
+            if current.chapters.contains_key(source) {
+                continue;
+            }
+            debug!("Removing outputs of {}", source.display());
+            let entry = &record.entry;
+            let paths = [&entry.page_path, &entry.fragment_path]
+                .into_iter()
+                .chain(&entry.extended_path);
+            for path in paths {
+                let path = self.output_dir.join(path);
+                if path.is_file() {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Hash the book-wide inputs that affect every chapter's output.
+    ///
+    /// Covers the crate version, config, book metadata, builtin templates,
+    /// theme files, the chapter tree shown in navigation and the asset
+    /// manifest. The builtin templates are hashed because a development
+    /// build may change them without changing the version.
+    fn context_hash(&self, hidden: &HashSet<PathBuf>, assets: &AssetManifest) -> Result<String> {
+        let mut parts: Vec<Vec<u8>> = vec![
+            env!("CARGO_PKG_VERSION").as_bytes().to_vec(),
+            serde_json::to_vec(&self.config)?,
+            serde_json::to_vec(&self.ctx.config.book)?,
+            assets.to_json()?.into_bytes(),
+        ];
+
+        let mut hidden: Vec<_> = hidden.iter().collect();
+        hidden.sort();
+        parts.push(serde_json::to_vec(&hidden)?);
+
+        for (name, content) in templates::BUILTIN_TEMPLATES {
+            parts.push(name.as_bytes().to_vec());
+            parts.push(content.as_bytes().to_vec());
+        }
+
+        let mut tree = String::new();
+        nav_signature(&self.ctx.book.sections, 0, &mut tree);
+        parts.push(tree.into_bytes());
+
+        if let Some(theme_dir) = &self.config.theme_dir {
+            let theme_dir = self.ctx.root.join(theme_dir);
+            for entry in WalkDir::new(&theme_dir).sort_by_file_name() {
+                let entry = entry?;
+                if entry.file_type().is_file() {
+                    parts.push(entry.path().to_string_lossy().into_owned().into_bytes());
+                    parts.push(fs::read(entry.path())?);
+                }
+            }
+        }
+
+        Ok(cache::hash_inputs(parts.iter().map(Vec::as_slice)))
+    }
+
     /// Create output directory structure.
     fn create_directories(&self) -> Result<()> {
         let dirs = ["pages", "fragments", "assets", "oob"];
@@ -214,23 +570,152 @@ impl HtmxRenderer {
         Ok(())
     }
 
+    /// Collect the source paths of chapters with `hidden: true` frontmatter.
+    fn collect_hidden(&self, chapters: &[&Chapter]) -> Result<HashSet<PathBuf>> {
+        let mut hidden = HashSet::new();
+
+        for chapter in chapters {
+            let Some(ref path) = chapter.path else {
+                continue;
+            };
+            let (frontmatter, _) = frontmatter::parse_frontmatter(&chapter.content, path)?;
+            if frontmatter.hidden {
+                hidden.insert(path.clone());
+            }
+        }
+
+        Ok(hidden)
+    }
+
+    /// Copy additional assets and chapter images into `assets/`, and write
+    /// the highlight stylesheet.
+    ///
+    /// Writes `assets-manifest.json` mapping logical paths to output paths and
+    /// SRI hashes.
+    fn process_assets(
+        &self,
+        chapters: &[&Chapter],
+        highlighter: Option<&Highlighter>,
+    ) -> Result<AssetManifest> {
+        let mut pipeline = AssetPipeline::new(
+            &self.config.assets,
+            &self.output_dir,
+            &self.config.base_url(),
+        );
+
+        // Copy non-template files from the theme directory (e.g., theme.css)
+        if let Some(ref dir) = self.config.theme_dir {
+            pipeline.add_dir_filtered(&self.ctx.root.join(dir), |path| {
+                path.extension().is_none_or(|ext| ext != "html")
+            })?;
+        }
+
+        // Highlight theme stylesheet (a theme directory highlight.css wins)
+        if let Some(highlighter) = highlighter {
+            pipeline.add_bytes(Path::new(HIGHLIGHT_CSS), highlighter.css()?.as_bytes())?;
+        }
+
+        // Copy the additional assets directory
+        if let Some(ref dir) = self.config.assets.additional_assets {
+            let dir = self.ctx.root.join(dir);
+            if !dir.is_dir() {
+                return Err(BuildError::ConfigError(format!(
+                    "Additional assets directory not found: {}",
+                    dir.display()
+                ))
+                .into());
+            }
+            pipeline.add_dir(&dir)?;
+        }
+
+        // Copy images referenced by chapters
+        let src_dir = self.ctx.root.join(&self.ctx.config.book.src);
+        for chapter in chapters {
+            let Some(ref path) = chapter.path else {
+                continue;
+            };
+            let (_, content) = frontmatter::parse_frontmatter(&chapter.content, path)?;
+
+            for url in render::extract_image_urls(content) {
+                let Some(logical) = assets::resolve_relative_url(path, &url) else {
+                    continue;
+                };
+                let source = src_dir.join(&logical);
+                if source.is_file() {
+                    pipeline.add_file(&logical, &source)?;
+                } else {
+                    warn!("Image not found: {} (in {})", url, path.display());
+                }
+            }
+        }
+
+        let manifest = pipeline.finish()?;
+        self.write_asset_manifest(&manifest)?;
+        Ok(manifest)
+    }
+
     /// Render a single chapter.
     fn render_chapter(
         &self,
         chapter: &Chapter,
         prev: Option<&Chapter>,
         next: Option<&Chapter>,
-        all_chapters: &[&Chapter],
+        state: &RenderState,
     ) -> Result<RenderedChapter> {
         let path = chapter.path.as_ref().unwrap();
+        let hidden = &state.hidden;
 
         // Parse frontmatter
         let (frontmatter, content) = frontmatter::parse_frontmatter(&chapter.content, path)?;
 
-        // Convert Markdown to HTML
-        let html_content = render::markdown_to_html(content);
+        // Convert Markdown to HTML, pointing local images at the copied assets
+        let image_url = |url: &str| {
+            assets::resolve_relative_url(path, url)
+                .and_then(|logical| state.assets.get_path(&logical))
+                .map(|entry| entry.url.clone())
+        };
+        let link_url = |url: &str| {
+            render::resolve_chapter_link(path, url)
+                .map(|route| url_with_base(&self.config.base_url(), &route))
+        };
+        let html = render::markdown_to_split_html(
+            content,
+            &MarkdownOptions {
+                image_url: Some(&image_url),
+                link_url: Some(&link_url),
+                htmx: Some(&self.config),
+                highlighter: state.highlighter.as_ref(),
+                permalinks: self.config.navigation.permalinks,
+                math: self.config.math.enabled,
+            },
+            frontmatter.extended_from.as_deref(),
+        );
+        if let (Some(anchor), None) = (&frontmatter.extended_from, &html.extended) {
+            warn!(
+                "{}: `extended_from` names no top-level heading with id `{}`",
+                path.display(),
+                anchor
+            );
+        }
+        // Full-only builds have no fragments to load the extended part into
+        let lazy = html.extended.is_some() && self.config.output_mode != OutputMode::Full;
+        let headings = render::extract_headings(content, self.config.math.enabled);
+        let toc = if self.config.navigation.toc {
+            build_toc(&headings, self.config.navigation.toc_depth)
+        } else {
+            Vec::new()
+        };
+        // A lazy fragment's TOC leaves out the extended part's headings until
+        // the extended fragment swaps in the full TOC
+        let main_toc = if lazy {
+            let main = &headings[..html.main_headings.min(headings.len())];
+            build_toc(main, self.config.navigation.toc_depth)
+        } else {
+            toc.clone()
+        };
 
         // Build template context
+        let base_url = self.config.base_url();
         let mut context = tera::Context::new();
 
         // Page context
@@ -239,15 +724,18 @@ impl HtmxRenderer {
             &serde_json::json!({
                 "title": frontmatter.title.as_ref().unwrap_or(&chapter.name),
                 "description": frontmatter.description,
-                "content": html_content,
-                "path": self.path_to_url(path),
+                "content": html.main,
+                "extended": html.extended,
+                "path": self.page_url(path),
                 "source_path": chapter.source_path,
                 "scopes": frontmatter.scope.as_ref().map(|s| vec![s.clone()]).unwrap_or_default(),
+                "toc": toc,
                 "meta": {
                     "description": frontmatter.description,
                 },
                 "htmx": {
-                    "lazy": false,
+                    "lazy": lazy,
+                    "extended_path": lazy.then(|| manifest::extended_url(&self.page_url(path))),
                 }
             }),
         );
@@ -261,11 +749,13 @@ impl HtmxRenderer {
                     "description": self.ctx.config.book.description,
                     "language": self.ctx.config.book.language,
                 },
+                "base_url": base_url,
                 "htmx": {
                     "boost": self.config.boost,
                     "target": self.config.target,
                     "swap_strategy": self.config.swap_strategy.to_string(),
                     "push_url": self.config.push_url,
+                    "highlight": self.config.highlight.enabled,
                     "navigation": {
                         "breadcrumbs": self.config.navigation.breadcrumbs,
                         "toc": self.config.navigation.toc,
@@ -282,7 +772,7 @@ impl HtmxRenderer {
                     "prev_page",
                     &serde_json::json!({
                         "title": prev.name,
-                        "path": self.path_to_url(prev_path),
+                        "path": self.page_url(prev_path),
                     }),
                 );
             }
@@ -294,36 +784,15 @@ impl HtmxRenderer {
                     "next_page",
                     &serde_json::json!({
                         "title": next.name,
-                        "path": self.path_to_url(next_path),
+                        "path": self.page_url(next_path),
                     }),
                 );
             }
         }
 
         // Build navigation sidebar context
-        let active_path = self.path_to_url(path);
-        let nav_items: Vec<NavItem> = all_chapters
-            .iter()
-            .filter_map(|ch| {
-                ch.path.as_ref().map(|p| {
-                    let url = self.path_to_url(p);
-                    let is_active = url == active_path;
-                    NavItem {
-                        title: ch.name.clone(),
-                        path: url.clone(),
-                        is_active,
-                        children: vec![],
-                        is_expanded: is_active || active_path.starts_with(&url),
-                        number: ch.number.as_ref().map(|nums| {
-                            nums.iter()
-                                .map(|n| n.to_string())
-                                .collect::<Vec<_>>()
-                                .join(".")
-                        }),
-                    }
-                })
-            })
-            .collect();
+        let active_path = self.page_url(path);
+        let nav_items = build_nav_items(&self.ctx.book.sections, &base_url, &active_path, hidden);
 
         let sidebar_ctx = SidebarContext {
             items: nav_items,
@@ -335,36 +804,115 @@ impl HtmxRenderer {
             &serde_json::json!({ "items": sidebar_ctx.items }),
         );
 
+        // Breadcrumb trail for full pages
+        if self.config.navigation.breadcrumbs {
+            let crumbs =
+                build_breadcrumbs(chapter, &self.ctx.book.sections, &base_url, &active_path);
+            context.insert("breadcrumb", &BreadcrumbContext { crumbs });
+        }
+
         // Generate OOB updates for fragment
-        let oob_updates =
-            render_oob_updates(&self.tera, chapter, all_chapters, &active_path).unwrap_or_default();
+        let mut oob_updates = render_oob_updates(
+            &state.tera,
+            chapter,
+            &self.ctx.book.sections,
+            &base_url,
+            &active_path,
+            hidden,
+        )
+        .unwrap_or_default();
+        if self.config.navigation.toc {
+            oob_updates.toc = render_toc_oob(&state.tera, &main_toc);
+        }
         context.insert("oob_updates", &oob_updates.to_html());
 
+        // Resolve the template pair (frontmatter `template` or the default `docs` set)
+        let (page_template, fragment_template) =
+            self.resolve_templates(frontmatter.template.as_deref(), chapter)?;
+
         // Render full page
-        let page = self
+        let page = state
             .tera
-            .render("docs/page.html", &context)
+            .render(&page_template, &context)
             .with_context(|| format!("Failed to render page template for {}", path.display()))?;
 
         // Render fragment
-        let fragment = self
+        let fragment = state
             .tera
-            .render("docs/fragment.html", &context)
+            .render(&fragment_template, &context)
             .with_context(|| {
                 format!("Failed to render fragment template for {}", path.display())
             })?;
 
+        // Render the extended fragment, which brings the full TOC
+        let extended = if lazy {
+            let toc_oob = if self.config.navigation.toc {
+                render_toc_oob(&state.tera, &toc)
+            } else {
+                None
+            };
+            context.insert("oob_updates", &toc_oob.unwrap_or_default());
+            let extended = state
+                .tera
+                .render(EXTENDED_TEMPLATE, &context)
+                .with_context(|| {
+                    format!("Failed to render extended template for {}", path.display())
+                })?;
+            Some(extended)
+        } else {
+            None
+        };
+
         // Compute output path (convert .md to .html)
         let output_path = path.with_extension("html");
 
         Ok(RenderedChapter {
             page,
             fragment,
+            extended,
+            content: html.main + html.extended.as_deref().unwrap_or_default(),
+            headings,
             frontmatter,
             path: output_path,
         })
     }
 
+    /// Resolve the page and fragment templates for a chapter.
+    ///
+    /// A template set `name` maps to `{name}/page.html` and `{name}/fragment.html`,
+    /// looked up among the builtin and theme templates. Without a frontmatter
+    /// `template`, the builtin `docs` set is used.
+    fn resolve_templates(
+        &self,
+        template: Option<&str>,
+        chapter: &Chapter,
+    ) -> Result<(String, String), BuildError> {
+        let set = template.unwrap_or(DEFAULT_TEMPLATE_SET);
+        let page = format!("{}/page.html", set);
+        let fragment = format!("{}/fragment.html", set);
+
+        for name in [&page, &fragment] {
+            if self.tera.get_template(name).is_err() {
+                return Err(BuildError::TemplateError {
+                    template: name.clone(),
+                    source: tera::Error::msg(format!(
+                        "template `{}` requested by chapter '{}' ({}) was not found",
+                        set,
+                        chapter.name,
+                        chapter
+                            .source_path
+                            .as_ref()
+                            .or(chapter.path.as_ref())
+                            .map(|p| p.display().to_string())
+                            .unwrap_or_default()
+                    )),
+                });
+            }
+        }
+
+        Ok((page, fragment))
+    }
+
     /// Write rendered chapter to output files.
     fn write_chapter(&self, rendered: &RenderedChapter) -> Result<()> {
         // Write full page
@@ -372,7 +920,7 @@ impl HtmxRenderer {
         if let Some(parent) = page_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::write(&page_path, &rendered.page)
+        cache::write_if_changed(&page_path, rendered.page.as_bytes())
             .with_context(|| format!("Failed to write page: {}", page_path.display()))?;
 
         // Write fragment if configured
@@ -381,9 +929,28 @@ impl HtmxRenderer {
             if let Some(parent) = fragment_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::write(&fragment_path, &rendered.fragment).with_context(|| {
-                format!("Failed to write fragment: {}", fragment_path.display())
-            })?;
+            cache::write_if_changed(&fragment_path, rendered.fragment.as_bytes()).with_context(
+                || format!("Failed to write fragment: {}", fragment_path.display()),
+            )?;
+
+            // Write the extended fragment, or remove one left from a split
+            let extended_path = self
+                .output_dir
+                .join("fragments")
+                .join(extended_file(&rendered.path));
+            if let Some(ref extended) = rendered.extended {
+                cache::write_if_changed(&extended_path, extended.as_bytes()).with_context(
+                    || {
+                        format!(
+                            "Failed to write extended fragment: {}",
+                            extended_path.display()
+                        )
+                    },
+                )?;
+            } else if extended_path.is_file() {
+                fs::remove_file(&extended_path)
+                    .with_context(|| format!("Failed to remove {}", extended_path.display()))?;
+            }
         }
 
         Ok(())
@@ -393,38 +960,189 @@ impl HtmxRenderer {
     fn write_manifest(&self, manifest: &Manifest) -> Result<()> {
         let path = self.output_dir.join("manifest.json");
         let json = manifest.to_json()?;
-        fs::write(&path, json)
+        cache::write_if_changed(&path, json.as_bytes())
             .with_context(|| format!("Failed to write manifest: {}", path.display()))?;
         info!("Wrote manifest.json with {} pages", manifest.pages.len());
         Ok(())
     }
 
+    /// Write assets-manifest.json.
+    fn write_asset_manifest(&self, manifest: &AssetManifest) -> Result<()> {
+        let path = self.output_dir.join("assets-manifest.json");
+        let json = manifest.to_json()?;
+        cache::write_if_changed(&path, json.as_bytes())
+            .with_context(|| format!("Failed to write asset manifest: {}", path.display()))?;
+        info!(
+            "Wrote assets-manifest.json with {} assets",
+            manifest.assets.len()
+        );
+        Ok(())
+    }
+
+    /// Write book.json.
+    fn write_book_json(&self, book: &BookData) -> Result<()> {
+        let path = self.output_dir.join("book.json");
+        let json = book.to_json()?;
+        cache::write_if_changed(&path, json.as_bytes())
+            .with_context(|| format!("Failed to write book data: {}", path.display()))?;
+        info!("Wrote book.json");
+        Ok(())
+    }
+
     /// Write search-index.json.
     fn write_search_index(&self, index: &SearchIndex) -> Result<()> {
         let path = self.output_dir.join("search-index.json");
         let json = index.to_json()?;
-        fs::write(&path, json)
+        cache::write_if_changed(&path, json.as_bytes())
             .with_context(|| format!("Failed to write search index: {}", path.display()))?;
         info!(
             "Wrote search-index.json with {} documents",
             index.documents.len()
         );
+
+        #[cfg(feature = "sqlite-search")]
+        if self.config.search.sqlite {
+            let path = self.output_dir.join("search.db");
+            if index.write_sqlite(&path)? {
+                info!("Wrote search.db");
+            } else {
+                debug!("search.db is unchanged");
+            }
+        }
+
         Ok(())
     }
 
+    /// Write one search index per audience: public, authenticated, verified
+    /// and each `authz` role.
+    ///
+    /// A shard holds the documents whose pages the audience may open under
+    /// the same rules `serve` enforces. Each role gets two shards for a user
+    /// holding only that role: one unverified and one with a verified email,
+    /// so pages requiring both `verified` and the role are searchable.
+    fn write_search_shards(
+        &self,
+        index: &SearchIndex,
+        manifest: &Manifest,
+    ) -> Result<SearchShards> {
+        let shard = |user: Option<&User>| {
+            index.filter(|doc| {
+                manifest.pages.get(&doc.path).is_some_and(|entry| {
+                    check_access(entry, user, &self.config.authz) == Access::Allowed
+                })
+            })
+        };
+
+        let signed_in = User {
+            roles: Some(Vec::new()),
+            verified: false,
+        };
+        let verified = User {
+            verified: true,
+            ..signed_in.clone()
+        };
+
+        let mut shards = SearchShards {
+            public: self.write_search_shard("public", &shard(None))?,
+            authenticated: self.write_search_shard("authenticated", &shard(Some(&signed_in)))?,
+            verified: self.write_search_shard("verified", &shard(Some(&verified)))?,
+            roles: BTreeMap::new(),
+            verified_roles: BTreeMap::new(),
+        };
+
+        let roles: BTreeSet<&String> = manifest
+            .pages
+            .values()
+            .filter_map(|entry| entry.authz.as_ref())
+            .flatten()
+            .collect();
+        for role in roles {
+            let user = User {
+                roles: Some(vec![role.clone()]),
+                verified: false,
+            };
+            let name = format!("role.{}", shard_name(role));
+            let file = self.write_search_shard(&name, &shard(Some(&user)))?;
+            shards.roles.insert(role.clone(), file);
+
+            let user = User {
+                verified: true,
+                ..user
+            };
+            let name = format!("{}.verified", name);
+            let file = self.write_search_shard(&name, &shard(Some(&user)))?;
+            shards.verified_roles.insert(role.clone(), file);
+        }
+
+        Ok(shards)
+    }
+
+    /// Write `search-index.{name}.json` and return its file name.
+    fn write_search_shard(&self, name: &str, index: &SearchIndex) -> Result<String> {
+        let file = format!("search-index.{}.json", name);
+        let path = self.output_dir.join(&file);
+        let json = index.to_json()?;
+        cache::write_if_changed(&path, json.as_bytes())
+            .with_context(|| format!("Failed to write search shard: {}", path.display()))?;
+        debug!("Wrote {} with {} documents", file, index.documents.len());
+        Ok(file)
+    }
+
     /// Convert file path to URL path.
     fn path_to_url(&self, path: &Path) -> String {
-        let path_str = path.with_extension("").to_string_lossy().to_string();
+        render::oob::path_to_url(path)
+    }
 
-        // Handle index pages
-        if path_str == "README" || path_str.ends_with("/README") {
-            if path_str == "README" {
-                "/".to_string()
+    /// Convert file path to the URL browsers link to, under the base path.
+    fn page_url(&self, path: &Path) -> String {
+        url_with_base(&self.config.base_url(), &self.path_to_url(path))
+    }
+}
+
+/// File-name-safe form of a role name.
+///
+/// Names with other characters than ASCII letters, digits, `-` and `_` have
+/// them replaced by `_` and get a hash suffix, so distinct roles never share
+/// a file.
+fn shard_name(role: &str) -> String {
+    let safe: String = role
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
             } else {
-                format!("/{}/", path_str.trim_end_matches("/README"))
+                '_'
             }
-        } else {
-            format!("/{}", path_str.replace('\\', "/"))
+        })
+        .collect();
+
+    if safe == role && !role.is_empty() {
+        safe
+    } else {
+        format!("{}-{}", safe, assets::compute_short_hash(role.as_bytes()))
+    }
+}
+
+/// Extended fragment file of a chapter's output path: `guide/intro.html`
+/// becomes `guide/intro.extended.html`.
+fn extended_file(path: &Path) -> PathBuf {
+    path.with_extension("extended.html")
+}
+
+/// Describe the chapter tree as used by navigation (names, paths, numbers
+/// and nesting, without content).
+fn nav_signature(items: &[BookItem], depth: usize, out: &mut String) {
+    for item in items {
+        match item {
+            BookItem::Chapter(ch) => {
+                out.push_str(&format!(
+                    "{}|{}|{:?}|{:?}\n",
+                    depth, ch.name, ch.path, ch.number
+                ));
+                nav_signature(&ch.sub_items, depth + 1, out);
+            }
+            BookItem::Separator => out.push_str(&format!("{}|---\n", depth)),
+            BookItem::PartTitle(title) => out.push_str(&format!("{}|#{}\n", depth, title)),
         }
     }
 }
@@ -445,6 +1163,336 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_templates() {
+        let mut renderer = create_test_renderer();
+        renderer.tera = templates::init_templates(None).unwrap();
+        renderer
+            .tera
+            .add_raw_templates(vec![
+                ("landing/page.html", "<main>{{ page.title }}</main>"),
+                ("landing/fragment.html", "{{ page.title }}"),
+            ])
+            .unwrap();
+        let chapter = Chapter {
+            name: "Welcome".to_string(),
+            content: String::new(),
+            path: Some(PathBuf::from("welcome.md")),
+            source_path: Some(PathBuf::from("welcome.md")),
+            number: None,
+            sub_items: vec![],
+            parent_names: vec![],
+        };
+
+        let (page, fragment) = renderer.resolve_templates(None, &chapter).unwrap();
+        assert_eq!(page, "docs/page.html");
+        assert_eq!(fragment, "docs/fragment.html");
+
+        let (page, fragment) = renderer
+            .resolve_templates(Some("landing"), &chapter)
+            .unwrap();
+        assert_eq!(page, "landing/page.html");
+        assert_eq!(fragment, "landing/fragment.html");
+
+        let err = renderer
+            .resolve_templates(Some("api-overview"), &chapter)
+            .unwrap_err();
+        assert!(matches!(err, BuildError::TemplateError { .. }));
+        let message = format!("{}: {}", err, std::error::Error::source(&err).unwrap());
+        assert!(message.contains("api-overview/page.html"));
+        assert!(message.contains("Welcome"));
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 40);
+        renderer.config.timestamps = false;
+
+        let mut build = |out: &str| {
+            renderer.output_dir = dir.path().join(out);
+            renderer.render().unwrap();
+            [
+                "search-index.json",
+                "book.json",
+                "manifest.json",
+                "pages/ch07.html",
+            ]
+            .map(|f| fs::read_to_string(renderer.output_dir.join(f)).unwrap())
+        };
+        let first = build("a");
+        let second = build("b");
+        assert_eq!(first, second);
+
+        // Search documents follow book order
+        let index: SearchIndex = serde_json::from_str(&first[0]).unwrap();
+        let paths: Vec<_> = index.documents.iter().map(|d| d.path.clone()).collect();
+        let expected: Vec<_> = (1..=40).map(|n| format!("/ch{:02}", n)).collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn test_incremental_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 5);
+        renderer.output_dir = dir.path().join("out");
+        let out = renderer.output_dir.clone();
+        let modified = |file: &str| fs::metadata(out.join(file)).unwrap().modified().unwrap();
+
+        renderer.render().unwrap();
+        assert!(out.join(cache::CACHE_FILE).is_file());
+        let before = ["pages/ch01.html", "pages/ch02.html", "manifest.json"].map(modified);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        // Edit one chapter's body: only that chapter is rewritten
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[1] else {
+            unreachable!()
+        };
+        ch.content.push_str("\n\nMore text.");
+        renderer.render().unwrap();
+        assert_eq!(modified("pages/ch01.html"), before[0]);
+        assert_ne!(modified("pages/ch02.html"), before[1]);
+        let book = fs::read_to_string(out.join("book.json")).unwrap();
+        assert!(book.contains("More text."));
+
+        // Renaming a chapter changes every sidebar
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[4] else {
+            unreachable!()
+        };
+        ch.name = "Renamed".to_string();
+        renderer.render().unwrap();
+        let page = fs::read_to_string(out.join("pages/ch01.html")).unwrap();
+        assert!(page.contains("Renamed"));
+    }
+
+    #[test]
+    fn test_scope_trees() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 3);
+        renderer.output_dir = dir.path().join("out");
+        renderer
+            .config
+            .scopes
+            .insert("public".to_string(), ScopeConfig::default());
+        let set_scope = |renderer: &mut HtmxRenderer, idx: usize, n: u32| {
+            let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[idx] else {
+                unreachable!()
+            };
+            ch.content = format!("---\nscope: internal\n---\n# Secret {}", n);
+        };
+        set_scope(&mut renderer, 1, 2);
+        renderer.render().unwrap();
+
+        let out = dir.path().join("out");
+        let public = out.join("scopes/public");
+        assert!(out.join("pages/ch02.html").is_file());
+        assert!(public.join("pages/ch01.html").is_file());
+        assert!(!public.join("pages/ch02.html").exists());
+
+        let manifest: Manifest =
+            serde_json::from_str(&fs::read_to_string(public.join("manifest.json")).unwrap())
+                .unwrap();
+        assert_eq!(manifest.scope.as_deref(), Some("public"));
+        assert_eq!(
+            manifest.pages.keys().collect::<Vec<_>>(),
+            ["/ch01", "/ch03"]
+        );
+        for file in ["search-index.json", "book.json", "pages/ch01.html"] {
+            let text = fs::read_to_string(public.join(file)).unwrap();
+            assert!(
+                !text.contains("Chapter 2") && !text.contains("Secret"),
+                "{}",
+                file
+            );
+        }
+        let page = fs::read_to_string(public.join("pages/ch01.html")).unwrap();
+        assert!(page.contains("Chapter 3"));
+
+        // A chapter moved out of the scope is removed from its tree
+        set_scope(&mut renderer, 2, 3);
+        renderer.render().unwrap();
+        assert!(!public.join("pages/ch03.html").exists());
+        assert!(out.join("pages/ch03.html").is_file());
+    }
+
+    #[test]
+    fn test_search_shards() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 5);
+        renderer.output_dir = dir.path().join("out");
+        let frontmatter = [
+            "authn: authenticated",
+            "authz: [staff]",
+            "authn: verified",
+            "authn: verified\nauthz: [staff]",
+        ];
+        for (idx, fm) in frontmatter.iter().enumerate() {
+            let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[idx + 1] else {
+                unreachable!()
+            };
+            ch.content = format!("---\n{}\n---\n{}", fm, ch.content);
+        }
+        renderer.render().unwrap();
+
+        let out = dir.path().join("out");
+        let manifest: Manifest =
+            serde_json::from_str(&fs::read_to_string(out.join("manifest.json")).unwrap()).unwrap();
+        let shards = manifest.search.unwrap();
+        assert_eq!(shards.roles.keys().collect::<Vec<_>>(), ["staff"]);
+
+        let paths = |file: &str| -> Vec<String> {
+            let index: SearchIndex =
+                serde_json::from_str(&fs::read_to_string(out.join(file)).unwrap()).unwrap();
+            index.documents.into_iter().map(|d| d.path).collect()
+        };
+        assert_eq!(paths(&shards.public), ["/ch01"]);
+        assert_eq!(paths(&shards.authenticated), ["/ch01", "/ch02"]);
+        assert_eq!(paths(&shards.verified), ["/ch01", "/ch02", "/ch04"]);
+        assert_eq!(paths(&shards.roles["staff"]), ["/ch01", "/ch02", "/ch03"]);
+        assert_eq!(shards.roles["staff"], "search-index.role.staff.json");
+        // Pages needing `verified` and a role are only in the verified role shard
+        assert_eq!(
+            paths(&shards.verified_roles["staff"]),
+            ["/ch01", "/ch02", "/ch03", "/ch04", "/ch05"]
+        );
+        assert_eq!(
+            shards.verified_roles["staff"],
+            "search-index.role.staff.verified.json"
+        );
+    }
+
+    #[test]
+    fn test_link_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 2);
+        renderer.output_dir = dir.path().join("out");
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[0] else {
+            unreachable!()
+        };
+        ch.content = "---\ntitle: One\n---\n# Chapter 1\n\n## Setup\n\n\
+                      [ok](ch02.md) [bad](ch02.md#nope)\n[self](#setup) [gone](ch09.md)"
+            .to_string();
+
+        // Warnings only by default
+        renderer.render().unwrap();
+
+        renderer.config.link_check.mode = LinkCheckMode::Fail;
+        let err = renderer.render().unwrap_err().to_string();
+        assert!(err.starts_with("2 broken link(s):"), "{}", err);
+        assert!(err.contains("ch01.md:8: `ch02.md#nope`: no heading with id `nope`"));
+        assert!(err.contains("ch01.md:9: `ch09.md`: no page at /ch09"));
+
+        renderer.config.link_check.enabled = false;
+        renderer.render().unwrap();
+    }
+
+    #[test]
+    fn test_lazy_extended_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 2);
+        renderer.output_dir = dir.path().join("out");
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[0] else {
+            unreachable!()
+        };
+        ch.content =
+            "# Chapter 1\n\nShort.\n\n<!-- extended -->\n\n## Details\n\nLong.".to_string();
+        renderer.render().unwrap();
+
+        let out = &renderer.output_dir;
+        let manifest = fs::read_to_string(out.join("manifest.json")).unwrap();
+        let manifest: Manifest = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(
+            manifest.pages["/ch01"].extended_path.as_deref(),
+            Some(Path::new("fragments/ch01.extended.html"))
+        );
+        assert_eq!(manifest.pages["/ch02"].extended_path, None);
+
+        let fragment = fs::read_to_string(out.join("fragments/ch01.html")).unwrap();
+        assert!(fragment.contains(r#"hx-get="&#x2F;ch01&#x2F;extended""#));
+        assert!(!fragment.contains("Long."));
+        let extended = fs::read_to_string(out.join("fragments/ch01.extended.html")).unwrap();
+        assert!(extended.contains(r#"<h2 id="details">Details"#));
+        assert!(extended.contains("<p>Long.</p>"));
+        // The extended headings join the TOC once their part has loaded
+        assert!(fragment.contains(r#"id="page-toc""#) && !fragment.contains("#details"));
+        assert!(extended.contains(r#"id="page-toc""#) && extended.contains("#details"));
+        // Full pages include both parts
+        let page = fs::read_to_string(out.join("pages/ch01.html")).unwrap();
+        assert!(page.contains("<p>Short.</p>") && page.contains("<p>Long.</p>"));
+
+        // Frontmatter picks the heading; unsplitting removes the old file
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[0] else {
+            unreachable!()
+        };
+        ch.content = "---\nextended_from: details\n---\n# Chapter 1\n\n## Details".to_string();
+        renderer.render().unwrap();
+        let extended = fs::read_to_string(out.join("fragments/ch01.extended.html")).unwrap();
+        assert!(extended.contains(r#"<h2 id="details">Details"#));
+
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[0] else {
+            unreachable!()
+        };
+        ch.content = "# Chapter 1".to_string();
+        renderer.render().unwrap();
+        assert!(!out.join("fragments/ch01.extended.html").exists());
+    }
+
+    #[test]
+    fn test_base_path_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut renderer = create_book_renderer(dir.path(), 3);
+        renderer.output_dir = dir.path().join("out");
+        renderer.config.base_path = "/docs/".to_string();
+        let BookItem::Chapter(ch) = &mut renderer.ctx.book.sections[1] else {
+            unreachable!()
+        };
+        ch.content = "# Chapter 2\n\nSee [three](ch03.md#setup).".to_string();
+        renderer.render().unwrap();
+
+        let out = &renderer.output_dir;
+        let page = fs::read_to_string(out.join("pages/ch02.html")).unwrap();
+        let fragment = fs::read_to_string(out.join("fragments/ch02.html")).unwrap();
+        for html in [&page, &fragment] {
+            // Sidebar, prev/next and the Home crumb
+            assert!(html.contains(r#"href="&#x2F;docs&#x2F;ch01""#));
+            assert!(html.contains(r#"href="&#x2F;docs&#x2F;ch03""#));
+            assert!(html.contains(r#"href="&#x2F;docs&#x2F;""#));
+            assert!(!html.contains(r#"href="&#x2F;ch0"#));
+            // Rewritten chapter link
+            assert!(html.contains(r#"<a href="/docs/ch03#setup""#));
+        }
+        assert!(page.contains(r#"data-path="&#x2F;docs&#x2F;ch02""#));
+    }
+
+    #[test]
+    fn test_shard_name() {
+        assert_eq!(shard_name("staff"), "staff");
+        assert!(shard_name("org:admin").starts_with("org_admin-"));
+        assert_ne!(shard_name("org:admin"), shard_name("org/admin"));
+    }
+
+    fn create_book_renderer(root: &Path, chapters: u32) -> HtmxRenderer {
+        let sections = (1..=chapters)
+            .map(|n| {
+                BookItem::Chapter(Chapter {
+                    name: format!("Chapter {}", n),
+                    content: format!("# Chapter {}\n\nBody of chapter {}.", n, n),
+                    path: Some(PathBuf::from(format!("ch{:02}.md", n))),
+                    source_path: Some(PathBuf::from(format!("ch{:02}.md", n))),
+                    number: Some(vec![n]),
+                    sub_items: vec![],
+                    parent_names: vec![],
+                })
+            })
+            .collect();
+
+        let mut renderer = create_test_renderer();
+        renderer.ctx.root = root.to_path_buf();
+        renderer.ctx.book.sections = sections;
+        renderer.tera = templates::init_templates(None).unwrap();
+        renderer
+    }
+
     fn create_test_renderer() -> HtmxRenderer {
         HtmxRenderer {
             ctx: RenderContext {
@@ -467,6 +1515,7 @@ mod tests {
             config: HtmxConfig::default(),
             tera: Tera::default(),
             output_dir: PathBuf::from("book/htmx"),
+            scope: None,
         }
     }
 }
@@ -0,0 +1,229 @@
+//! Build-time checking of links between chapters.
+//!
+//! Each rendered chapter records the links written in its Markdown (with
+//! their line numbers) and its heading anchors. Once every chapter is
+//! rendered, links to other chapters and `#anchors` are checked against the
+//! pages actually written, so links to missing or draft chapters and
+//! renamed headings are reported with their source file and line.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde::{Deserialize, Serialize};
+
+use crate::render;
+
+/// A link as written in a chapter's Markdown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    /// Line in the source file (1-based)
+    pub line: usize,
+    /// Destination as written
+    pub url: String,
+}
+
+/// Extract the links from a chapter's Markdown.
+///
+/// # Arguments
+/// * `markdown` - The Markdown source
+/// * `first_line` - Line number of the first line of `markdown` in its file
+///   (after frontmatter)
+pub fn extract_links(markdown: &str, first_line: usize) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut line = first_line;
+    let mut counted = 0;
+
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_HEADING_ATTRIBUTES,
+    );
+    for (event, range) in parser.into_offset_iter() {
+        if let Event::Start(Tag::Link { dest_url, .. }) = event {
+            line += markdown[counted..range.start].matches('\n').count();
+            counted = range.start;
+            links.push(Link {
+                line,
+                url: dest_url.to_string(),
+            });
+        }
+    }
+
+    links
+}
+
+/// Why a link is broken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The linked file is outside the source directory
+    OutsideBook,
+    /// No page was written for the linked chapter
+    MissingPage(String),
+    /// The linked page has no heading with this anchor
+    MissingAnchor(String),
+}
+
+/// A broken link found by [`LinkChecker::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// Source file of the linking chapter
+    pub source: PathBuf,
+    /// Line of the link in `source`
+    pub line: usize,
+    /// Destination as written
+    pub url: String,
+    /// What is wrong with it
+    pub problem: Problem,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: `{}`: ",
+            self.source.display(),
+            self.line,
+            self.url
+        )?;
+        match &self.problem {
+            Problem::OutsideBook => write!(f, "points outside the book"),
+            Problem::MissingPage(url) => write!(f, "no page at {}", url),
+            Problem::MissingAnchor(anchor) => write!(f, "no heading with id `{}`", anchor),
+        }
+    }
+}
+
+/// Checks links against the pages and heading anchors of a build.
+#[derive(Debug, Default)]
+pub struct LinkChecker<'a> {
+    /// Heading anchors keyed by page URL
+    pages: HashMap<&'a str, HashSet<&'a str>>,
+}
+
+impl<'a> LinkChecker<'a> {
+    /// Register a written page and its heading anchors.
+    pub fn add_page(&mut self, url_path: &'a str, anchors: impl IntoIterator<Item = &'a str>) {
+        self.pages.entry(url_path).or_default().extend(anchors);
+    }
+
+    /// Check the links of one chapter.
+    ///
+    /// Only links to other chapters' `.md` files and same-page `#anchors`
+    /// are checked; external, absolute and asset links are skipped.
+    ///
+    /// # Arguments
+    /// * `chapter_path` - Chapter path relative to the source directory
+    /// * `url_path` - URL of the chapter's page
+    /// * `source` - Source file reported for broken links
+    /// * `links` - The chapter's links
+    pub fn check(
+        &self,
+        chapter_path: &Path,
+        url_path: &str,
+        source: &Path,
+        links: &[Link],
+    ) -> Vec<BrokenLink> {
+        links
+            .iter()
+            .filter_map(|link| {
+                let problem = self.problem(chapter_path, url_path, &link.url)?;
+                Some(BrokenLink {
+                    source: source.to_path_buf(),
+                    line: link.line,
+                    url: link.url.clone(),
+                    problem,
+                })
+            })
+            .collect()
+    }
+
+    /// What is wrong with `url`, if it is checked and broken.
+    fn problem(&self, chapter_path: &Path, url_path: &str, url: &str) -> Option<Problem> {
+        let target = if let Some(anchor) = url.strip_prefix('#') {
+            format!("{}#{}", url_path, anchor)
+        } else {
+            let file = url.split(['?', '#']).next().unwrap_or(url);
+            if !file.ends_with(".md") || url.starts_with('/') || url.contains("://") {
+                return None;
+            }
+            match render::resolve_chapter_link(chapter_path, url) {
+                Some(target) => target,
+                None => return Some(Problem::OutsideBook),
+            }
+        };
+
+        let (page, anchor) = match target.split_once('#') {
+            Some((page, anchor)) => (page, Some(anchor)),
+            None => (target.as_str(), None),
+        };
+        let Some(anchors) = self.pages.get(page) else {
+            return Some(Problem::MissingPage(page.to_string()));
+        };
+        match anchor {
+            Some(anchor) if !anchor.is_empty() && !anchors.contains(anchor) => {
+                Some(Problem::MissingAnchor(anchor.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_links() {
+        let md = "# Title\n\nSee [a](a.md) and\n[b](b.md#x).\n\n```\n[not](a link)\n```\n[c](#top)";
+        let links = extract_links(md, 5);
+        let found: Vec<_> = links.iter().map(|l| (l.line, l.url.as_str())).collect();
+        assert_eq!(found, [(7, "a.md"), (8, "b.md#x"), (13, "#top")]);
+    }
+
+    #[test]
+    fn test_check() {
+        let mut checker = LinkChecker::default();
+        checker.add_page("/guide/install", ["setup"]);
+        checker.add_page("/guide/", []);
+
+        let links: Vec<_> = [
+            "install.md#setup",
+            "README.md",
+            "install.md#missing",
+            "usage.md",
+            "../../outside.md",
+            "#intro",
+            "https://example.com/x.md",
+            "../images/logo.png",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, url)| Link {
+            line: i + 1,
+            url: url.to_string(),
+        })
+        .collect();
+
+        let broken = checker.check(
+            Path::new("guide/README.md"),
+            "/guide/",
+            Path::new("guide/README.md"),
+            &links,
+        );
+        let problems: Vec<_> = broken.iter().map(|b| (b.line, &b.problem)).collect();
+        assert_eq!(
+            problems,
+            [
+                (3, &Problem::MissingAnchor("missing".to_string())),
+                (4, &Problem::MissingPage("/guide/usage".to_string())),
+                (5, &Problem::OutsideBook),
+                (6, &Problem::MissingAnchor("intro".to_string())),
+            ]
+        );
+        assert_eq!(
+            broken[1].to_string(),
+            "guide/README.md:4: `usage.md`: no page at /guide/usage"
+        );
+    }
+}
@@ -5,19 +5,89 @@
 //! - Authorization metadata for access control
 //! - Audience-scoped content filtering
 //! - SPA-like navigation without JavaScript frameworks
+//!
+//! Run without arguments it acts as an mdBook backend. `mdbook-htmx serve`
+//! starts a development server for the build output.
 
 use std::io::{self, Read};
+use std::path::PathBuf;
 
 use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
 use log::{debug, error, info};
 
+use mdbook_htmx::serve::auth::JwtKeys;
+use mdbook_htmx::serve::{DevServer, ServeOptions};
 use mdbook_htmx::HtmxRenderer;
 
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve a build output directory for local development
+    Serve {
+        /// Build output directory
+        #[arg(default_value = "book/htmx")]
+        dir: PathBuf,
+        /// Address to bind
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 3000)]
+        port: u16,
+        /// Book directory containing book.toml
+        #[arg(long, default_value = ".")]
+        book: PathBuf,
+        /// URL prefix the book was built with (overrides `base-path`)
+        #[arg(long)]
+        base_path: Option<String>,
+        /// HMAC secret for verifying JWTs
+        #[arg(long, env = "MDBOOK_HTMX_JWT_SECRET", hide_env_values = true)]
+        jwt_secret: Option<String>,
+        /// JWKS file with public keys for verifying JWTs
+        #[arg(long, conflicts_with = "jwt_secret")]
+        jwks: Option<PathBuf>,
+    },
+}
+
 fn main() -> Result<()> {
     env_logger::init();
 
     info!("mdbook-htmx v{}", env!("CARGO_PKG_VERSION"));
 
+    match Cli::parse().command {
+        Some(Command::Serve {
+            dir,
+            host,
+            port,
+            book,
+            base_path,
+            jwt_secret,
+            jwks,
+        }) => {
+            let mut options = ServeOptions::from_book_dir(dir, &book)?;
+            if let Some(base_path) = base_path {
+                options.config.base_path = base_path;
+                options.config.validate()?;
+            }
+            options.keys = match (jwt_secret, jwks) {
+                (Some(secret), _) => Some(JwtKeys::Secret(secret.into_bytes())),
+                (None, Some(path)) => Some(JwtKeys::from_jwks_file(&path)?),
+                (None, None) => None,
+            };
+            DevServer::new(options)?.run(&format!("{}:{}", host, port))
+        }
+        None => render_from_stdin(),
+    }
+}
+
+/// Run as an mdBook backend.
+fn render_from_stdin() -> Result<()> {
     // MDBook passes RenderContext via stdin as JSON
     let mut stdin = io::stdin();
     let mut input = String::new();
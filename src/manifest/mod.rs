@@ -1,8 +1,10 @@
 //! Manifest generation for server-side integration.
 //!
 //! Generates manifest.json containing page metadata for server use.
+//! Pages are keyed in sorted order so identical builds produce identical
+//! files.
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
@@ -17,11 +19,43 @@ pub struct Manifest {
     /// Manifest format version
     pub version: String,
 
-    /// Build timestamp (ISO 8601)
-    pub generated_at: String,
+    /// Build timestamp (ISO 8601), omitted when timestamps are disabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>,
 
-    /// Map of URL paths to page entries
-    pub pages: HashMap<String, PageEntry>,
+    /// Audience scope this tree was published for (None for the full book)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+
+    /// Map of URL paths to page entries, sorted by path
+    pub pages: BTreeMap<String, PageEntry>,
+
+    /// Pre-filtered search index files (when search shards are enabled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchShards>,
+}
+
+/// Search index shards, each holding only the documents one audience may
+/// open. File names are relative to the output directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchShards {
+    /// Shard for anonymous visitors
+    pub public: String,
+
+    /// Shard for signed-in users without a listed role
+    pub authenticated: String,
+
+    /// Shard for signed-in users with a verified email
+    pub verified: String,
+
+    /// Shard per `authz` role for users without a verified email, keyed by
+    /// role name
+    pub roles: BTreeMap<String, String>,
+
+    /// Shard per `authz` role for users with a verified email, keyed by
+    /// role name
+    #[serde(default)]
+    pub verified_roles: BTreeMap<String, String>,
 }
 
 /// Metadata for a single page.
@@ -39,6 +73,11 @@ pub struct PageEntry {
     /// Path to content fragment
     pub fragment_path: PathBuf,
 
+    /// Path to the lazily loaded extended fragment, served at
+    /// [`extended_url`] (chapters split for lazy loading only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extended_path: Option<PathBuf>,
+
     /// Audience scope (if set)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scope: Option<String>,
@@ -57,6 +96,10 @@ pub struct PageEntry {
 
     /// Content hash for cache invalidation
     pub content_hash: String,
+
+    /// Hidden from navigation and search (still reachable by URL)
+    #[serde(default)]
+    pub hidden: bool,
 }
 
 impl Manifest {
@@ -65,8 +108,10 @@ impl Manifest {
         Self {
             schema: "https://schemas.arusty.dev/mdbook-htmx/manifest.schema.json".to_string(),
             version: "1.0.0".to_string(),
-            generated_at: chrono::Utc::now().to_rfc3339(),
-            pages: HashMap::new(),
+            generated_at: Some(build_timestamp()),
+            scope: None,
+            pages: BTreeMap::new(),
+            search: None,
         }
     }
 
@@ -81,8 +126,76 @@ impl Manifest {
     }
 }
 
+/// URL of a page's extended fragment: the page URL followed by `/extended`.
+pub fn extended_url(url_path: &str) -> String {
+    format!("{}/extended", url_path.trim_end_matches('/'))
+}
+
+/// Timestamp for build outputs (RFC 3339).
+///
+/// Honors `SOURCE_DATE_EPOCH` for reproducible builds and falls back to
+/// the current time.
+pub fn build_timestamp() -> String {
+    source_date_epoch(std::env::var("SOURCE_DATE_EPOCH").ok().as_deref())
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339())
+}
+
+/// Format a `SOURCE_DATE_EPOCH` value, if it is a valid Unix timestamp.
+fn source_date_epoch(value: Option<&str>) -> Option<String> {
+    let secs = value?.trim().parse::<i64>().ok()?;
+    let time = chrono::DateTime::from_timestamp(secs, 0)?;
+    Some(time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
 impl Default for Manifest {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str) -> PageEntry {
+        PageEntry {
+            title: title.to_string(),
+            source: PathBuf::from(format!("{}.md", title)),
+            page_path: PathBuf::from(format!("pages/{}.html", title)),
+            fragment_path: PathBuf::from(format!("fragments/{}.html", title)),
+            extended_path: None,
+            scope: None,
+            authn: None,
+            authz: None,
+            fallback: None,
+            content_hash: String::new(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_pages_are_sorted() {
+        let mut manifest = Manifest::new();
+        manifest.generated_at = None;
+        for title in ["zeta", "alpha", "mid"] {
+            manifest.add_page(format!("/{}", title), entry(title));
+        }
+
+        let json = manifest.to_json().unwrap();
+        let alpha = json.find("\"/alpha\"").unwrap();
+        let mid = json.find("\"/mid\"").unwrap();
+        let zeta = json.find("\"/zeta\"").unwrap();
+        assert!(alpha < mid && mid < zeta);
+        assert!(!json.contains("generated_at"));
+    }
+
+    #[test]
+    fn test_source_date_epoch() {
+        assert_eq!(
+            source_date_epoch(Some("1700000000")).as_deref(),
+            Some("2023-11-14T22:13:20Z")
+        );
+        assert_eq!(source_date_epoch(Some("not a number")), None);
+        assert_eq!(source_date_epoch(None), None);
+    }
+}
@@ -0,0 +1,192 @@
+//! GitHub-style callouts (admonitions).
+//!
+//! A blockquote whose first line is a `[!TYPE]` marker becomes a callout:
+//!
+//! ```markdown
+//! > [!WARNING] Breaking change
+//! > The `theme` option was renamed.
+//! ```
+//!
+//! renders as a `<div class="callout callout-warning">` with a title line
+//! holding an icon slot and the title (the text after the marker, or the
+//! type name). Any ASCII word is accepted as the type; the builtin layout
+//! styles GitHub's `NOTE`, `TIP`, `IMPORTANT`, `WARNING` and `CAUTION`.
+
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+use super::escape_html;
+
+/// Replace marked blockquotes in a Markdown event stream with callout HTML.
+///
+/// The marker and the title after it move into the callout's HTML, so
+/// consumers that only read text events see just the callout body. Use
+/// [`strip_markers`] to keep the title as text instead.
+pub fn convert_callouts(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    // For each open blockquote: whether it is a callout
+    let mut open = Vec::new();
+    let mut events = events.into_iter().peekable();
+
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::BlockQuote) => {
+                if !matches!(events.peek(), Some(Event::Start(Tag::Paragraph))) {
+                    open.push(false);
+                    output.push(event);
+                    continue;
+                }
+
+                // The first line's leading text, which may hold the marker
+                let paragraph = events.next().unwrap();
+                let mut texts = Vec::new();
+                while let Some(Event::Text(_)) = events.peek() {
+                    if let Some(Event::Text(text)) = events.next() {
+                        texts.push(text);
+                    }
+                }
+                let line: String = texts.iter().map(|text| text.as_ref()).collect();
+
+                let Some((kind, rest)) = parse_marker(&line) else {
+                    open.push(false);
+                    output.push(event);
+                    output.push(paragraph);
+                    output.extend(texts.into_iter().map(Event::Text));
+                    continue;
+                };
+
+                // A custom title needs the whole first line to be plain text
+                let line_ends = matches!(
+                    events.peek(),
+                    Some(Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph))
+                );
+                let title = match rest.trim() {
+                    title if line_ends && !title.is_empty() => title.to_string(),
+                    _ => default_title(&kind),
+                };
+                output.push(Event::Html(CowStr::from(open_html(&kind, &title))));
+                open.push(true);
+
+                match events.peek() {
+                    // Marker on a line of its own: drop the line break
+                    Some(Event::SoftBreak | Event::HardBreak) => {
+                        events.next();
+                        output.push(paragraph);
+                    }
+                    // Marker is the whole paragraph: drop the paragraph
+                    Some(Event::End(TagEnd::Paragraph)) => {
+                        events.next();
+                    }
+                    // Inline markup follows on the marker line: keep it as body
+                    _ => {
+                        output.push(paragraph);
+                        let rest = rest.trim_start();
+                        if !rest.is_empty() {
+                            output.push(Event::Text(CowStr::from(rest.to_string())));
+                        }
+                    }
+                }
+            }
+            Event::End(TagEnd::BlockQuote) => {
+                if open.pop() == Some(true) {
+                    output.push(Event::Html(CowStr::from("</div>\n")));
+                } else {
+                    output.push(event);
+                }
+            }
+            event => output.push(event),
+        }
+    }
+
+    output
+}
+
+/// Remove the `[!TYPE]` marker from marked blockquotes, keeping the rest of
+/// their first line.
+///
+/// For consumers that read a callout's text, such as the search index: a
+/// custom title stays in the text and only the marker is dropped.
+pub fn strip_markers(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut events = events.into_iter().peekable();
+
+    while let Some(event) = events.next() {
+        let quote = matches!(event, Event::Start(Tag::BlockQuote));
+        output.push(event);
+        if !quote || !matches!(events.peek(), Some(Event::Start(Tag::Paragraph))) {
+            continue;
+        }
+        output.extend(events.next());
+
+        let mut texts = Vec::new();
+        while let Some(Event::Text(_)) = events.peek() {
+            if let Some(Event::Text(text)) = events.next() {
+                texts.push(text);
+            }
+        }
+        let line: String = texts.iter().map(|text| text.as_ref()).collect();
+
+        match parse_marker(&line) {
+            Some((_, rest)) => output.push(Event::Text(CowStr::from(rest.to_string()))),
+            None => output.extend(texts.into_iter().map(Event::Text)),
+        }
+    }
+
+    output
+}
+
+/// Split `[!TYPE] rest` into the lowercase type and the rest of the line.
+fn parse_marker(line: &str) -> Option<(String, &str)> {
+    let (kind, rest) = line.trim_start().strip_prefix("[!")?.split_once(']')?;
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((kind.to_ascii_lowercase(), rest))
+}
+
+/// Title for a callout without one: its type, capitalized.
+fn default_title(kind: &str) -> String {
+    let mut chars = kind.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+/// Opening tags of a callout, up to and including its title.
+fn open_html(kind: &str, title: &str) -> String {
+    format!(
+        "<div class=\"callout callout-{}\" role=\"note\">\n\
+         <p class=\"callout-title\"><span class=\"callout-icon\" aria-hidden=\"true\"></span>{}</p>\n",
+        kind,
+        escape_html(title)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::markdown_to_html;
+
+    #[test]
+    fn test_callouts() {
+        let html = markdown_to_html("> [!NOTE]\n> Read *this* first.");
+        assert!(html.starts_with(
+            "<div class=\"callout callout-note\" role=\"note\">\n\
+             <p class=\"callout-title\"><span class=\"callout-icon\" aria-hidden=\"true\"></span>Note</p>\n\
+             <p>Read <em>this</em> first.</p>\n</div>"
+        ));
+        assert!(!html.contains("[!NOTE]"));
+
+        let html = markdown_to_html("> [!warning] Breaking change\n> Renamed.");
+        assert!(html.contains(r#"<div class="callout callout-warning""#));
+        assert!(html.contains("</span>Breaking change</p>\n<p>Renamed.</p>"));
+
+        // A marker alone in its paragraph, nested inside a plain blockquote
+        let html = markdown_to_html("> Quote\n>\n> > [!TIP]\n> >\n> > Body");
+        assert!(html.starts_with("<blockquote>\n<p>Quote</p>\n<div class=\"callout callout-tip\""));
+        assert!(html.contains("</span>Tip</p>\n<p>Body</p>\n</div>\n</blockquote>"));
+
+        // Not a marker
+        let html = markdown_to_html("> [!not a type]\n> text");
+        assert!(html.starts_with("<blockquote>"));
+    }
+}
@@ -0,0 +1,227 @@
+//! mdBook-style fenced code blocks.
+//!
+//! Info strings are read the way mdBook's HTML renderer reads them: the
+//! first word is the language and the rest (`ignore`, `should_panic`,
+//! `editable`, ...) become classes on the `<code>` element. Hidden lines
+//! (`# ` in Rust, or the prefix set with `hidelines=<prefix>`) are wrapped
+//! in `<span class="boring">` and revealed with a toggle button.
+
+use super::{escape_html, Highlighter};
+
+/// Toggle button added to blocks with hidden lines.
+const TOGGLE_BUTTON: &str = concat!(
+    r#"<button type="button" class="code-toggle" aria-pressed="false" "#,
+    r#"aria-label="Show hidden lines" title="Show hidden lines">"#,
+    r#"<svg viewBox="0 0 24 24" width="16" height="16" aria-hidden="true">"#,
+    r#"<path d="M1 12s4-7 11-7 11 7 11 7-4 7-11 7S1 12 1 12z" fill="none" stroke="currentColor" stroke-width="2"/>"#,
+    r#"<circle cx="12" cy="12" r="3" fill="none" stroke="currentColor" stroke-width="2"/>"#,
+    r#"</svg></button>"#
+);
+
+/// A parsed code block info string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeInfo<'a> {
+    /// Language (first word), empty for plain blocks
+    pub language: &'a str,
+    /// Remaining words, such as `ignore` or `editable`
+    pub attributes: Vec<&'a str>,
+}
+
+impl<'a> CodeInfo<'a> {
+    /// Parse an info string such as `rust,ignore` or `python hidelines=~`.
+    ///
+    /// Words are separated by commas or whitespace.
+    pub fn parse(info: &'a str) -> Self {
+        let mut words = info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty());
+        Self {
+            language: words.next().unwrap_or_default(),
+            attributes: words.collect(),
+        }
+    }
+
+    /// Classes for the `<code>` element: `language-<lang>` and each
+    /// attribute that is a plain word.
+    pub fn classes(&self) -> String {
+        let language = (!self.language.is_empty()).then(|| format!("language-{}", self.language));
+        let attributes = self.attributes.iter().filter(|attr| {
+            attr.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+
+        language
+            .into_iter()
+            .chain(attributes.map(|attr| attr.to_string()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Prefix marking hidden lines: `hidelines=<prefix>`, or `#` for Rust.
+    pub fn hidden_prefix(&self) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find_map(|attr| attr.strip_prefix("hidelines="))
+            .or((self.language == "rust").then_some("#"))
+    }
+}
+
+/// A line of a code block, with its hiding prefix removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeLine {
+    /// Line text including its newline
+    pub text: String,
+    /// Hidden unless the reader shows hidden lines
+    pub hidden: bool,
+}
+
+/// Split a code block into lines, marking the hidden ones.
+///
+/// Rust follows rustdoc: `# ` and a lone `#` hide a line, `##` is an
+/// escaped `#`, and any other `#` line (`#[derive]`, `#foo` in a macro
+/// body) stays visible. Other prefixes hide every line that starts with
+/// them (after indentation).
+pub fn split_hidden_lines(code: &str, prefix: Option<&str>) -> Vec<CodeLine> {
+    code.split_inclusive('\n')
+        .map(|line| {
+            let rest = line.trim_start();
+            let indent = &line[..line.len() - rest.len()];
+            let visible = |text: String| CodeLine {
+                text,
+                hidden: false,
+            };
+            let hidden = |stripped: &str| CodeLine {
+                text: format!("{}{}", indent, stripped),
+                hidden: true,
+            };
+
+            match prefix {
+                Some("#") => match rest.strip_prefix('#') {
+                    Some(after) if after.starts_with('#') => {
+                        visible(format!("{}{}", indent, after))
+                    }
+                    Some(after) if after.trim_end_matches(['\r', '\n']).is_empty() => hidden(after),
+                    Some(after) if after.starts_with(' ') => hidden(&after[1..]),
+                    _ => visible(line.to_string()),
+                },
+                Some(prefix) => match rest.strip_prefix(prefix) {
+                    Some(after) => hidden(after),
+                    None => visible(line.to_string()),
+                },
+                None => visible(line.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Render a fenced or indented code block.
+///
+/// The code is highlighted when a highlighter is given and knows the
+/// language; blocks with hidden lines get a toggle button.
+pub fn render_code_block(code: &str, info: &CodeInfo, highlighter: Option<&Highlighter>) -> String {
+    let lines = split_hidden_lines(code, info.hidden_prefix());
+    let source: String = lines.iter().map(|line| line.text.as_str()).collect();
+
+    let highlighted = highlighter
+        .filter(|_| !info.language.is_empty())
+        .and_then(|highlighter| highlighter.highlight_lines(&source, info.language));
+    let is_highlighted = highlighted.is_some();
+    let html_lines =
+        highlighted.unwrap_or_else(|| lines.iter().map(|line| escape_html(&line.text)).collect());
+
+    let has_hidden = lines.iter().any(|line| line.hidden);
+    let mut body = String::with_capacity(source.len());
+    for (line, html) in lines.iter().zip(&html_lines) {
+        if line.hidden {
+            body.push_str(&format!("<span class=\"boring\">{}</span>", html));
+        } else {
+            body.push_str(html);
+        }
+    }
+
+    let pre_classes: Vec<_> = [(is_highlighted, "hl-code"), (has_hidden, "has-hidden")]
+        .into_iter()
+        .filter_map(|(on, class)| on.then_some(class))
+        .collect();
+    let mut html = String::from("<pre");
+    if !pre_classes.is_empty() {
+        html.push_str(&format!(" class=\"{}\"", pre_classes.join(" ")));
+    }
+    html.push('>');
+    if has_hidden {
+        html.push_str(TOGGLE_BUTTON);
+    }
+    html.push_str("<code");
+    let classes = info.classes();
+    if !classes.is_empty() {
+        html.push_str(&format!(" class=\"{}\"", escape_html(&classes)));
+    }
+    html.push_str(&format!(">{}</code></pre>\n", body));
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_info() {
+        let info = CodeInfo::parse("rust,ignore editable");
+        assert_eq!(info.language, "rust");
+        assert_eq!(info.classes(), "language-rust ignore editable");
+        assert_eq!(info.hidden_prefix(), Some("#"));
+
+        let info = CodeInfo::parse("python hidelines=~");
+        assert_eq!(info.classes(), "language-python");
+        assert_eq!(info.hidden_prefix(), Some("~"));
+
+        assert_eq!(CodeInfo::parse("").classes(), "");
+        assert_eq!(CodeInfo::parse("toml").hidden_prefix(), None);
+    }
+
+    #[test]
+    fn test_split_hidden_lines() {
+        let code =
+            "# use std::fmt;\n#\n#[derive(Debug)]\n    # let x = 1;\n## not hidden\nfn main() {}\n";
+        let lines: Vec<_> = split_hidden_lines(code, Some("#"))
+            .into_iter()
+            .map(|line| (line.hidden, line.text))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (true, "use std::fmt;\n".to_string()),
+                (true, "\n".to_string()),
+                (false, "#[derive(Debug)]\n".to_string()),
+                (true, "    let x = 1;\n".to_string()),
+                (false, "# not hidden\n".to_string()),
+                (false, "fn main() {}\n".to_string()),
+            ]
+        );
+
+        // `#ident` in macro bodies is code, not a hidden line
+        let lines = split_hidden_lines("quote! {\n    #foo::new(#ident)\n}\n#", Some("#"));
+        assert!(!lines[0].hidden);
+        assert!(!lines[1].hidden && lines[1].text == "    #foo::new(#ident)\n");
+        assert!(lines[3].hidden && lines[3].text.is_empty());
+
+        let lines = split_hidden_lines("~import os\nprint(1)", Some("~"));
+        assert!(lines[0].hidden && lines[0].text == "import os\n");
+        assert!(!lines[1].hidden);
+    }
+
+    #[test]
+    fn test_render_code_block() {
+        let code = "# fn main() {\nlet x = \"<a>\";\n# }\n";
+        let html = render_code_block(code, &CodeInfo::parse("rust,should_panic"), None);
+        assert!(html
+            .starts_with(r#"<pre class="has-hidden"><button type="button" class="code-toggle""#));
+        assert!(html.contains(r#"<code class="language-rust should_panic">"#));
+        assert!(html.contains(
+            "<span class=\"boring\">fn main() {\n</span>let x = &quot;&lt;a&gt;&quot;;\n"
+        ));
+
+        let html = render_code_block("a < b\n", &CodeInfo::default(), None);
+        assert_eq!(html, "<pre><code>a &lt; b\n</code></pre>\n");
+    }
+}
@@ -0,0 +1,134 @@
+//! Build-time syntax highlighting for fenced code blocks.
+//!
+//! Code is tokenized with syntect and emitted as `<span>`s with `hl-`
+//! prefixed classes, so the colours live in `assets/highlight.css` and
+//! swapped fragments need no client-side highlighter.
+
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::config::HighlightConfig;
+use crate::BuildError;
+
+/// Class prefix for highlighted tokens.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Highlights code blocks with the bundled syntax definitions.
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    /// Load the syntax definitions and the configured theme.
+    pub fn new(config: &HighlightConfig) -> Result<Self, BuildError> {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let Some(theme) = themes.remove(&config.theme) else {
+            let mut names: Vec<_> = themes.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            return Err(BuildError::ConfigError(format!(
+                "Unknown highlight theme '{}'. Available themes: {}",
+                config.theme,
+                names.join(", ")
+            )));
+        };
+
+        Ok(Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        })
+    }
+
+    /// Highlight `code` as `language` (a name or file extension).
+    ///
+    /// Returns the inner HTML of the `<code>` element, or None when the
+    /// language is unknown.
+    pub fn highlight(&self, code: &str, language: &str) -> Option<String> {
+        self.highlight_lines(code, language)
+            .map(|lines| lines.concat())
+    }
+
+    /// Highlight `code` line by line.
+    ///
+    /// Each line's HTML (including its newline) closes every span it opens
+    /// and reopens the ones carried over from the previous line, so lines
+    /// can be wrapped individually (e.g., to hide them).
+    pub fn highlight_lines(&self, code: &str, language: &str) -> Option<Vec<String>> {
+        let syntax = self.syntaxes.find_syntax_by_token(language)?;
+        let mut parse_state = ParseState::new(syntax);
+        let mut stack = ScopeStack::new();
+
+        let mut lines = Vec::new();
+        for line in LinesWithEndings::from(code) {
+            let ops = parse_state.parse_line(line, &self.syntaxes).ok()?;
+            let mut html: String = stack
+                .as_slice()
+                .iter()
+                .map(|&scope| open_span(scope))
+                .collect();
+            let (spans, _) =
+                line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack).ok()?;
+            html.push_str(&spans);
+            html.push_str(&"</span>".repeat(stack.len()));
+            lines.push(html);
+        }
+        Some(lines)
+    }
+
+    /// Stylesheet for the theme, written to `assets/highlight.css`.
+    pub fn css(&self) -> Result<String, BuildError> {
+        css_for_theme_with_class_style(&self.theme, CLASS_STYLE).map_err(|e| {
+            BuildError::ConfigError(format!("Failed to generate highlight CSS: {}", e))
+        })
+    }
+}
+
+/// Opening tag for a scope, with the classes syntect gives it.
+fn open_span(scope: Scope) -> String {
+    let ClassStyle::SpacedPrefixed { prefix } = CLASS_STYLE else {
+        unreachable!()
+    };
+    let classes: Vec<_> = scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("{}{}", prefix, atom))
+        .collect();
+    format!("<span class=\"{}\">", classes.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        let highlighter = Highlighter::new(&HighlightConfig::default()).unwrap();
+
+        let html = highlighter.highlight("fn main() {}\n", "rust").unwrap();
+        assert!(html.contains(r#"<span class="hl-storage hl-type hl-function hl-rust">fn</span>"#));
+        assert!(highlighter.highlight("x", "no-such-language").is_none());
+
+        // Lines stand alone: the string's spans are reopened on line two
+        let lines = highlighter
+            .highlight_lines("let s = \"a\nb\";\n", "rust")
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("a\n</span></span>"));
+        assert!(lines[1].starts_with(r#"<span class="hl-source hl-rust"><span class="hl-string hl-quoted hl-double hl-rust">b"#));
+
+        let css = highlighter.css().unwrap();
+        assert!(css.contains(".hl-code"));
+    }
+
+    #[test]
+    fn test_unknown_theme() {
+        let config = HighlightConfig {
+            theme: "Nope".to_string(),
+            ..Default::default()
+        };
+        let err = Highlighter::new(&config).err().unwrap();
+        assert!(err.to_string().contains("InspiredGitHub"));
+    }
+}
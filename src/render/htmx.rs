@@ -6,24 +6,15 @@ use crate::config::{HtmxConfig, SwapStrategy};
 
 /// Inject HTMX attributes into rendered HTML.
 ///
-/// Adds:
-/// - `hx-boost="true"` on `<body>` (if boost enabled)
-/// - `hx-target` and `hx-swap` defaults
-/// - `hx-push-url="true"` on navigation links (if push_url enabled)
+/// Adds `hx-boost="true"` and the `hx-target`/`hx-swap` defaults on
+/// `<body>` (if boost enabled). Links within chapter content get their
+/// attributes when the Markdown is rendered (see [`content_link_attrs`]).
 pub fn inject_htmx_attrs(html: &str, config: &HtmxConfig) -> String {
-    let mut html = html.to_string();
-
-    // Add hx-boost to body
     if config.boost {
-        html = inject_body_attrs(&html, config);
-    }
-
-    // Add hx-push-url to navigation links
-    if config.push_url {
-        html = inject_push_url(&html);
+        inject_body_attrs(html, config)
+    } else {
+        html.to_string()
     }
-
-    html
 }
 
 /// Convert SwapStrategy to its HTMX string representation.
@@ -64,14 +55,44 @@ fn inject_body_attrs(html: &str, config: &HtmxConfig) -> String {
     html.to_string()
 }
 
-/// Inject `hx-push-url="true"` on navigation links.
+/// Generate HTMX attributes for a link to another chapter in page content.
 ///
-/// Targets links with class `nav-link` or within navigation elements.
-fn inject_push_url(html: &str) -> String {
-    // For now, we rely on template-level attributes rather than post-processing.
-    // The templates include hx-push-url on navigation links.
-    // This function is here for future enhancements like link processing.
-    html.to_string()
+/// Mirrors the sidebar links: the routed `url` is fetched into the content
+/// target and pushed to the history. The request goes to the page without
+/// its `#anchor`, which stays in the pushed URL; after the swap the window
+/// scrolls to the linked heading, or to the top when there is no anchor.
+pub fn content_link_attrs(url: &str, config: &HtmxConfig) -> String {
+    let (page, anchor) = match url.split_once('#') {
+        Some((page, anchor)) => (page, Some(anchor)),
+        None => (url, None),
+    };
+    let show = match anchor {
+        Some(anchor) if is_simple_id(anchor) => format!("#{}", anchor),
+        _ => "window".to_string(),
+    };
+
+    let mut attrs = format!(
+        "hx-get=\"{}\" hx-target=\"{}\" hx-swap=\"{} show:{}:top\"",
+        page,
+        config.target,
+        swap_strategy_to_str(&config.swap_strategy),
+        show
+    );
+
+    if config.push_url {
+        let push = if anchor.is_some() { url } else { "true" };
+        attrs.push_str(&format!(" hx-push-url=\"{}\"", push));
+    }
+
+    attrs
+}
+
+/// Whether `id` can be used as a `#id` selector without escaping.
+fn is_simple_id(id: &str) -> bool {
+    id.starts_with(|c: char| c.is_ascii_alphabetic())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
 
 /// Generate HTMX attributes for a navigation link.
@@ -117,9 +138,11 @@ mod tests {
     #[test]
     fn test_inject_body_attrs_custom_target() {
         let html = r#"<html><body><div>Content</div></body></html>"#;
-        let mut config = HtmxConfig::default();
-        config.target = "#main".to_string();
-        config.swap_strategy = SwapStrategy::OuterHTML;
+        let config = HtmxConfig {
+            target: "#main".to_string(),
+            swap_strategy: SwapStrategy::OuterHTML,
+            ..Default::default()
+        };
 
         let result = inject_htmx_attrs(html, &config);
 
@@ -130,8 +153,10 @@ mod tests {
     #[test]
     fn test_no_boost() {
         let html = r#"<html><body><div>Content</div></body></html>"#;
-        let mut config = HtmxConfig::default();
-        config.boost = false;
+        let config = HtmxConfig {
+            boost: false,
+            ..Default::default()
+        };
 
         let result = inject_htmx_attrs(html, &config);
 
@@ -147,6 +172,30 @@ mod tests {
         assert!(attrs.contains("hx-push-url=\"true\""));
     }
 
+    #[test]
+    fn test_content_link_attrs() {
+        let config = HtmxConfig::default();
+
+        let attrs = content_link_attrs("/guide/install#setup", &config);
+        assert!(attrs.contains(r#"hx-get="/guide/install""#));
+        assert!(attrs.contains(r##"hx-target="#content""##));
+        assert!(attrs.contains(r##"hx-swap="innerHTML show:#setup:top""##));
+        assert!(attrs.contains(r##"hx-push-url="/guide/install#setup""##));
+
+        let attrs = content_link_attrs("/guide/install#1-setup", &config);
+        assert!(attrs.contains(r#"hx-get="/guide/install""#));
+        assert!(attrs.contains("show:window:top"));
+
+        let attrs = content_link_attrs("/guide/install", &config);
+        assert!(attrs.contains(r#"hx-push-url="true""#));
+
+        let config = HtmxConfig {
+            push_url: false,
+            ..Default::default()
+        };
+        assert!(!content_link_attrs("/intro", &config).contains("hx-push-url"));
+    }
+
     #[test]
     fn test_preload_hint() {
         let hint = preload_hint("/chapter/intro");
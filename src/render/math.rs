@@ -0,0 +1,384 @@
+//! TeX math in Markdown.
+//!
+//! `$...$` is inline math and `$$...$$` display math; `\$` is a literal
+//! dollar sign. An inline formula must not start with whitespace, end with
+//! whitespace or be followed by a digit, so prices like "$5 and $10" stay
+//! text. Math inside code and raw HTML is left alone.
+//!
+//! Formulas are cut out of the Markdown before it is parsed (so emphasis
+//! and backslash escapes do not mangle them) and replaced by placeholders.
+//! After parsing, [`MathText::render`] turns the placeholders into MathML,
+//! while text consumers use [`MathText::restore`] or [`MathText::strip`].
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use log::warn;
+use pulldown_cmark::{CowStr, Event, Parser, Tag, TagEnd};
+
+use super::escape_html;
+use super::mathml::tex_to_mathml;
+
+/// Starts a placeholder, followed by the formula's index in decimal.
+const START: char = '\u{e000}';
+/// Ends a placeholder.
+const END: char = '\u{e001}';
+
+/// A formula cut out of the Markdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Formula {
+    /// Source including its `$` delimiters
+    source: String,
+    /// TeX between the delimiters
+    tex: String,
+    display: bool,
+}
+
+/// Markdown with its formulas replaced by placeholders.
+#[derive(Debug, Clone)]
+pub struct MathText<'a> {
+    /// The Markdown to parse
+    pub markdown: Cow<'a, str>,
+    formulas: Vec<Formula>,
+}
+
+impl<'a> MathText<'a> {
+    /// Cut the formulas out of `markdown` if math is `enabled`; otherwise
+    /// keep it as written, with no formulas.
+    pub fn new(markdown: &'a str, enabled: bool) -> Self {
+        if enabled {
+            Self::extract(markdown)
+        } else {
+            Self {
+                markdown: Cow::Borrowed(markdown),
+                formulas: Vec::new(),
+            }
+        }
+    }
+
+    /// Cut the formulas out of `markdown`.
+    pub fn extract(markdown: &'a str) -> Self {
+        let spans = find_math(markdown);
+        if spans.is_empty() {
+            return Self {
+                markdown: Cow::Borrowed(markdown),
+                formulas: Vec::new(),
+            };
+        }
+
+        let mut output = String::with_capacity(markdown.len());
+        let mut formulas = Vec::with_capacity(spans.len());
+        let mut last = 0;
+        for (n, (range, display)) in spans.into_iter().enumerate() {
+            let delimiter = if display { 2 } else { 1 };
+            output.push_str(&markdown[last..range.start]);
+            output.push_str(&format!("{}{}{}", START, n, END));
+            formulas.push(Formula {
+                source: markdown[range.clone()].to_string(),
+                tex: markdown[range.start + delimiter..range.end - delimiter].to_string(),
+                display,
+            });
+            last = range.end;
+        }
+        output.push_str(&markdown[last..]);
+
+        Self {
+            markdown: Cow::Owned(output),
+            formulas,
+        }
+    }
+
+    /// Put the formulas' source back into text from the parsed Markdown.
+    pub fn restore<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.replace(text, |formula| formula.source.clone())
+    }
+
+    /// Remove the formulas from text from the parsed Markdown.
+    pub fn strip<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.replace(text, |_| String::new())
+    }
+
+    /// Replace the formulas in a parsed event stream with MathML.
+    ///
+    /// A paragraph holding only a display formula becomes the bare `<math>`
+    /// block. Formulas that fail to convert are shown as their source in a
+    /// `<code class="math-error">` element, with a warning.
+    pub fn render<'e>(&self, events: Vec<Event<'e>>) -> Vec<Event<'e>> {
+        if self.formulas.is_empty() {
+            return events;
+        }
+
+        let mut output = Vec::with_capacity(events.len());
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            match event {
+                Event::Start(Tag::Paragraph) => {
+                    let Some(Event::Text(text)) = events.peek() else {
+                        output.push(event);
+                        continue;
+                    };
+                    let display = self
+                        .formula_at(text)
+                        .filter(|(formula, len)| formula.display && *len == text.len());
+                    match display {
+                        Some((formula, _)) => {
+                            let html = format!("{}\n", to_mathml(formula));
+                            events.next();
+                            if matches!(events.peek(), Some(Event::End(TagEnd::Paragraph))) {
+                                events.next();
+                                output.push(Event::Html(CowStr::from(html)));
+                            } else {
+                                output.push(event);
+                                output.push(Event::InlineHtml(CowStr::from(html)));
+                            }
+                        }
+                        None => output.push(event),
+                    }
+                }
+                Event::Text(text) if text.contains(START) => {
+                    let mut rest = text.as_ref();
+                    while let Some(start) = rest.find(START) {
+                        let Some((formula, len)) = self.formula_at(&rest[start..]) else {
+                            break;
+                        };
+                        if start > 0 {
+                            output.push(Event::Text(CowStr::from(rest[..start].to_string())));
+                        }
+                        output.push(Event::InlineHtml(CowStr::from(to_mathml(formula))));
+                        rest = &rest[start + len..];
+                    }
+                    if !rest.is_empty() {
+                        output.push(Event::Text(CowStr::from(rest.to_string())));
+                    }
+                }
+                Event::Html(html) if html.contains(START) => {
+                    let html = self.replace(&html, to_mathml).into_owned();
+                    output.push(Event::Html(CowStr::from(html)));
+                }
+                Event::InlineHtml(html) if html.contains(START) => {
+                    let html = self.replace(&html, to_mathml).into_owned();
+                    output.push(Event::InlineHtml(CowStr::from(html)));
+                }
+                event => output.push(event),
+            }
+        }
+
+        output
+    }
+
+    /// The formula whose placeholder starts `text`, and the placeholder length.
+    fn formula_at(&self, text: &str) -> Option<(&Formula, usize)> {
+        let rest = text.strip_prefix(START)?;
+        let end = rest.find(END)?;
+        let formula = self.formulas.get(rest[..end].parse::<usize>().ok()?)?;
+        Some((formula, START.len_utf8() + end + END.len_utf8()))
+    }
+
+    /// Replace each placeholder in `text`.
+    fn replace<'t>(&self, text: &'t str, with: impl Fn(&Formula) -> String) -> Cow<'t, str> {
+        if self.formulas.is_empty() || !text.contains(START) {
+            return Cow::Borrowed(text);
+        }
+
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(START) {
+            output.push_str(&rest[..start]);
+            match self.formula_at(&rest[start..]) {
+                Some((formula, len)) => {
+                    output.push_str(&with(formula));
+                    rest = &rest[start + len..];
+                }
+                None => {
+                    output.push(START);
+                    rest = &rest[start + START.len_utf8()..];
+                }
+            }
+        }
+        output.push_str(rest);
+        Cow::Owned(output)
+    }
+}
+
+/// MathML for a formula, or its source marked as an error.
+fn to_mathml(formula: &Formula) -> String {
+    match tex_to_mathml(&formula.tex, formula.display) {
+        Ok(mathml) => mathml,
+        Err(e) => {
+            warn!("Invalid math `{}`: {}", formula.source, e);
+            format!(
+                "<code class=\"math-error\" title=\"{}\">{}</code>",
+                escape_html(&e.to_string()),
+                escape_html(&formula.source)
+            )
+        }
+    }
+}
+
+/// Byte ranges of the formulas in `markdown`, and whether each is display math.
+fn find_math(markdown: &str) -> Vec<(Range<usize>, bool)> {
+    if !markdown.contains('$') {
+        return Vec::new();
+    }
+
+    // Code and raw HTML are never math
+    let skip: Vec<_> = Parser::new_ext(markdown, super::PARSER_OPTIONS)
+        .into_offset_iter()
+        .filter(|(event, _)| {
+            matches!(
+                event,
+                Event::Start(Tag::CodeBlock(_))
+                    | Event::Code(_)
+                    | Event::Html(_)
+                    | Event::InlineHtml(_)
+            )
+        })
+        .map(|(_, range)| range)
+        .collect();
+
+    let bytes = markdown.as_bytes();
+    let mut spans = Vec::new();
+    let mut skip = skip.iter().peekable();
+    let mut i = 0;
+    while i < bytes.len() {
+        while skip.next_if(|range| range.end <= i).is_some() {}
+        if let Some(range) = skip.peek().filter(|range| range.start <= i) {
+            i = range.end;
+            continue;
+        }
+        // A formula cannot run into code
+        let limit = skip.peek().map_or(bytes.len(), |range| range.start);
+
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'$' if bytes.get(i + 1) == Some(&b'$') => {
+                let end = markdown[i + 2..limit]
+                    .find("$$")
+                    .map(|end| i + 2 + end)
+                    .filter(|&end| !markdown[i + 2..end].trim().is_empty());
+                match end {
+                    Some(end) => {
+                        spans.push((i..end + 2, true));
+                        i = end + 2;
+                    }
+                    None => i += 2,
+                }
+            }
+            b'$' => match inline_end(&bytes[..limit], i) {
+                Some(end) => {
+                    spans.push((i..end + 1, false));
+                    i = end + 1;
+                }
+                None => i += 1,
+            },
+            _ => i += 1,
+        }
+    }
+
+    spans
+}
+
+/// Index of the `$` closing the inline formula opened at `start`.
+fn inline_end(bytes: &[u8], start: usize) -> Option<usize> {
+    if bytes.get(start + 1).is_none_or(|c| c.is_ascii_whitespace()) {
+        return None;
+    }
+
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            // A blank line ends the paragraph
+            b'\n'
+                if bytes[i + 1..]
+                    .iter()
+                    .find(|c| !matches!(c, b' ' | b'\t' | b'\r'))
+                    .is_none_or(|&c| c == b'\n') =>
+            {
+                return None;
+            }
+            b'$' if !bytes[i - 1].is_ascii_whitespace()
+                && !bytes.get(i + 1).is_some_and(u8::is_ascii_digit) =>
+            {
+                return Some(i);
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::MarkdownOptions;
+    use crate::render::{extract_headings, markdown_to_html, markdown_to_html_with};
+
+    fn render(markdown: &str) -> String {
+        let options = MarkdownOptions {
+            math: true,
+            ..Default::default()
+        };
+        markdown_to_html_with(markdown, &options)
+    }
+
+    #[test]
+    fn test_find_math() {
+        let md = "A $x^2$ and $$\\sum_i i$$, costs $5 and $10.";
+        let found: Vec<_> = find_math(md)
+            .into_iter()
+            .map(|(range, display)| (&md[range], display))
+            .collect();
+        assert_eq!(found, [("$x^2$", false), ("$$\\sum_i i$$", true)]);
+
+        for text in ["\\$a$", "`$code$`", "$ b$", "$c $", "$x$1"] {
+            assert!(find_math(text).is_empty(), "{}", text);
+        }
+        // Inline math stops at a blank line; display math may span lines
+        assert!(find_math("$a\n\nb$").is_empty());
+        assert_eq!(find_math("$$\na\n$$").len(), 1);
+        assert!(find_math("```\n$x$\n```\n<span title=\"$y$\">").is_empty());
+    }
+
+    #[test]
+    fn test_render_math() {
+        let html = render("Energy is $E = mc^2$, where $a*b*c$.");
+        assert!(html.starts_with(
+            "<p>Energy is <math display=\"inline\" xmlns=\"http://www.w3.org/1998/Math/MathML\">"
+        ));
+        assert!(html.contains("<annotation encoding=\"application/x-tex\">a*b*c</annotation>"));
+        assert!(!html.contains("<em>"));
+
+        // A display formula on its own is not wrapped in a paragraph
+        let html = render("Text\n\n$$\n\\frac{1}{2}\n$$\n\nMore");
+        assert!(html.contains(
+            "<p>Text</p>\n<math display=\"block\" xmlns=\"http://www.w3.org/1998/Math/MathML\">"
+        ));
+        assert!(html.contains("</math>\n<p>More</p>"));
+
+        let html = render("Bad $\\frac{1}$ formula");
+        assert!(html.contains("<code class=\"math-error\""));
+        assert!(html.contains(r">$\frac{1}$</code>"));
+
+        assert_eq!(render("`$x$`"), "<p><code>$x$</code></p>\n");
+
+        // Math is off by default
+        assert_eq!(markdown_to_html("$a*b*c$"), "<p>$a<em>b</em>c$</p>\n");
+    }
+
+    #[test]
+    fn test_math_headings() {
+        let md = "## Energy $E=mc^2$";
+        let headings = extract_headings(md, true);
+        assert_eq!(
+            headings[0],
+            (2, "Energy $E=mc^2$".to_string(), "energy-emc2".to_string())
+        );
+        assert!(render(md).starts_with("<h2 id=\"energy-emc2\">Energy <math"));
+
+        let math = MathText::extract("a $x$ b");
+        assert_eq!(math.strip(&math.markdown), "a  b");
+        assert_eq!(math.restore(&math.markdown), "a $x$ b");
+    }
+}
@@ -0,0 +1,268 @@
+//! TeX to MathML conversion.
+//!
+//! Formulas are parsed and rendered by [`pulldown_latex`], which follows
+//! MathML Core. It covers the TeX math documentation uses (scripts,
+//! fractions, roots, symbols, fonts, `\left`/`\right`, matrix and
+//! alignment environments); see its documentation for the full list of
+//! commands.
+//!
+//! A formula that fails to parse is an error as a whole, so the caller can
+//! report it and show the source instead of half-rendered math.
+
+use std::fmt;
+
+use pulldown_latex::config::DisplayMode;
+use pulldown_latex::{push_mathml, Event, ParserError, RenderConfig, Storage};
+
+use super::escape_html;
+
+/// Error converting a formula.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TexError(String);
+
+impl fmt::Display for TexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TexError {}
+
+impl From<ParserError> for TexError {
+    fn from(error: ParserError) -> Self {
+        // The first line is the message; the rest quotes the source
+        let message = error.to_string();
+        let message = message.lines().next().unwrap_or_default();
+        Self(message.trim_start_matches("parsing error: ").to_string())
+    }
+}
+
+/// Convert a TeX formula (without `$` delimiters) to a `<math>` element.
+///
+/// The TeX source is kept in an `application/x-tex` annotation.
+pub fn tex_to_mathml(tex: &str, display: bool) -> Result<String, TexError> {
+    let storage = Storage::new();
+    let events = pulldown_latex::Parser::new(tex, &storage).collect::<Result<Vec<Event>, _>>()?;
+
+    let annotation = escape_html(tex.trim());
+    let config = RenderConfig {
+        display_mode: if display {
+            DisplayMode::Block
+        } else {
+            DisplayMode::Inline
+        },
+        annotation: Some(&annotation),
+        xml: true,
+        ..RenderConfig::default()
+    };
+
+    let mut mathml = String::new();
+    push_mathml(
+        &mut mathml,
+        events.into_iter().map(Ok::<_, ParserError>),
+        config,
+    )
+    .map_err(|e| TexError(e.to_string()))?;
+    Ok(escape_text(&mathml))
+}
+
+/// Escape `<`, `>` and `&` in the text between MathML tags.
+///
+/// `pulldown_latex` writes operators and function names as they are (e.g.
+/// `<mo><</mo>`), which an HTML parser may read as the start of a tag.
+/// Tags are the MathML elements it emits; entities it writes are kept.
+fn escape_text(mathml: &str) -> String {
+    let mut output = String::with_capacity(mathml.len());
+    let mut rest = mathml;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '<' if is_tag(&rest[1..]) => rest.find('>').map_or(rest.len(), |end| end + 1),
+            '&' if is_entity(&rest[1..]) => rest.find(';').map_or(rest.len(), |end| end + 1),
+            _ => 0,
+        };
+        if len > 0 {
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        match c {
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '&' => output.push_str("&amp;"),
+            c => output.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
+
+/// Whether the text after a `<` opens or closes a MathML element.
+///
+/// Text spelling out a whole tag (`\operatorname{<mi>}`) still reads as one.
+fn is_tag(after: &str) -> bool {
+    const ELEMENTS: &[&str] = &[
+        "annotation",
+        "math",
+        "menclose",
+        "merror",
+        "mfrac",
+        "mi",
+        "mmultiscripts",
+        "mn",
+        "mo",
+        "mover",
+        "mpadded",
+        "mphantom",
+        "mprescripts",
+        "mroot",
+        "mrow",
+        "ms",
+        "mspace",
+        "msqrt",
+        "mstyle",
+        "msub",
+        "msubsup",
+        "msup",
+        "mtable",
+        "mtd",
+        "mtext",
+        "mtr",
+        "munder",
+        "munderover",
+        "none",
+        "semantics",
+    ];
+    let after = after.strip_prefix('/').unwrap_or(after);
+    let end = after
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(after.len());
+    ELEMENTS.contains(&&after[..end]) && after[end..].starts_with([' ', '>', '/'])
+}
+
+/// Whether the text after a `&` is a character reference.
+fn is_entity(after: &str) -> bool {
+    let Some((name, _)) = after.split_once(';') else {
+        return false;
+    };
+    match name.strip_prefix('#') {
+        Some(number) => match number.strip_prefix(['x', 'X']) {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()),
+        },
+        None => !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Body of the `<math>` element, without the annotation.
+    fn body(tex: &str) -> String {
+        let html = tex_to_mathml(tex, false).unwrap();
+        let start = html.find("<semantics>").unwrap() + "<semantics>".len();
+        let end = html.find("<annotation").unwrap();
+        html[start..end].to_string()
+    }
+
+    #[test]
+    fn test_tex_to_mathml() {
+        let html = tex_to_mathml("a < b", true).unwrap();
+        assert!(html.starts_with(
+            r#"<math display="block" xmlns="http://www.w3.org/1998/Math/MathML"><semantics>"#
+        ));
+        assert!(html.ends_with(
+            r#"<annotation encoding="application/x-tex">a &lt; b</annotation></semantics></math>"#
+        ));
+        assert!(tex_to_mathml("x", false)
+            .unwrap()
+            .starts_with(r#"<math display="inline""#));
+
+        assert_eq!(
+            body("E = mc^2"),
+            "<mrow><mi>E</mi><mo>=</mo><mi>m</mi><msup><mi>c</mi><mn>2</mn></msup></mrow>"
+        );
+        assert!(body(r"\begin{pmatrix} a & b \\ c & d \end{pmatrix}").contains("<mtable"));
+    }
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(
+            body("a < b"),
+            "<mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow>"
+        );
+        assert_eq!(body(r"\&"), "<mrow><mi>&amp;</mi></mrow>");
+        // `<b` in a function name is text, not a `<b>` tag
+        assert_eq!(body(r"\operatorname{a<b}"), "<mrow><mi>a&lt;b</mi></mrow>");
+        assert_eq!(
+            body(r"\text{a<b&c}"),
+            "<mrow><mtext>a&lt;b&amp;c</mtext></mrow>"
+        );
+        assert_eq!(
+            escape_text("<mi>&nbsp;&#x2062;</mi>"),
+            "<mi>&nbsp;&#x2062;</mi>"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        for tex in [
+            "{x",
+            "x}",
+            r"\frac{a}",
+            r"\foo",
+            r"\left( x",
+            r"\begin{nope}x\end{nope}",
+        ] {
+            let error = tex_to_mathml(tex, false).unwrap_err().to_string();
+            assert!(
+                !error.is_empty() && !error.contains('\n'),
+                "{}: {}",
+                tex,
+                error
+            );
+        }
+    }
+
+    /// Whether every tag in `xml` is closed in order.
+    fn well_formed(xml: &str) -> bool {
+        let mut stack = Vec::new();
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            let Some(len) = rest[start..].find('>') else {
+                return false;
+            };
+            let tag = &rest[start + 1..start + len];
+            rest = &rest[start + len + 1..];
+            if let Some(name) = tag.strip_prefix('/') {
+                if stack.pop() != Some(name) {
+                    return false;
+                }
+            } else if !tag.ends_with('/') {
+                stack.push(tag.split(' ').next().unwrap_or(tag));
+            }
+        }
+        stack.is_empty()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_any_input(tex in "\\PC{0,40}") {
+            if let Ok(html) = tex_to_mathml(&tex, true) {
+                prop_assert!(well_formed(&html), "{} -> {}", tex, html);
+            }
+        }
+
+        // No `m`, so the text never spells out a MathML tag
+        #[test]
+        fn prop_markup_characters(tex in "[a-ln-z<>& +^_]{0,20}") {
+            for tex in [tex.clone(), format!(r"\operatorname{{{}}}", tex)] {
+                if let Ok(html) = tex_to_mathml(&tex, false) {
+                    prop_assert!(well_formed(&html), "{} -> {}", tex, html);
+                }
+            }
+        }
+    }
+}
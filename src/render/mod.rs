@@ -3,15 +3,60 @@
 //! Handles Markdown to HTML conversion using pulldown-cmark,
 //! HTMX attribute injection, and OOB swap generation.
 
+pub mod callout;
+pub mod code;
+pub mod highlight;
 pub mod htmx;
+pub mod math;
+pub mod mathml;
 pub mod oob;
 
-pub use htmx::{inject_htmx_attrs, nav_link_attrs, preload_hint};
+pub use code::CodeInfo;
+pub use highlight::Highlighter;
+pub use htmx::{content_link_attrs, inject_htmx_attrs, nav_link_attrs, preload_hint};
+pub use math::MathText;
 pub use oob::{
-    render_oob_updates, Breadcrumb, BreadcrumbContext, NavItem, OobUpdates, SidebarContext,
+    build_breadcrumbs, build_nav_items, build_toc, render_oob_updates, render_toc_oob, Breadcrumb,
+    BreadcrumbContext, NavItem, OobUpdates, SidebarContext, TocEntry,
 };
 
-use pulldown_cmark::{html, Options, Parser};
+use std::collections::HashSet;
+use std::path::Path;
+
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+
+use crate::assets;
+use crate::config::HtmxConfig;
+
+/// Markdown extensions for chapter content.
+///
+/// Everything that parses chapters for rendered output or its heading ids
+/// uses these, so the same headings and heading text are seen everywhere.
+pub(crate) const PARSER_OPTIONS: Options = Options::ENABLE_TABLES
+    .union(Options::ENABLE_FOOTNOTES)
+    .union(Options::ENABLE_STRIKETHROUGH)
+    .union(Options::ENABLE_TASKLISTS)
+    .union(Options::ENABLE_HEADING_ATTRIBUTES);
+
+/// Rewrites a URL found in Markdown, or returns None to keep it.
+pub type UrlRewriter<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// Options for [`markdown_to_html_with`].
+#[derive(Default)]
+pub struct MarkdownOptions<'a> {
+    /// Returns the replacement URL for an image, or None to keep it
+    pub image_url: Option<UrlRewriter<'a>>,
+    /// Returns the routed URL for a link to another chapter, or None to keep it
+    pub link_url: Option<UrlRewriter<'a>>,
+    /// Add HTMX attributes to rewritten links (see [`content_link_attrs`])
+    pub htmx: Option<&'a HtmxConfig>,
+    /// Highlights fenced code blocks when set
+    pub highlighter: Option<&'a Highlighter>,
+    /// Append a `#` permalink anchor to every heading
+    pub permalinks: bool,
+    /// Convert TeX math to MathML (see [`math`])
+    pub math: bool,
+}
 
 /// Convert Markdown content to HTML.
 ///
@@ -23,43 +68,335 @@ use pulldown_cmark::{html, Options, Parser};
 /// # Returns
 /// HTML string
 pub fn markdown_to_html(markdown: &str) -> String {
-    // Enable common extensions
-    let options = Options::ENABLE_TABLES
-        | Options::ENABLE_FOOTNOTES
-        | Options::ENABLE_STRIKETHROUGH
-        | Options::ENABLE_TASKLISTS
-        | Options::ENABLE_HEADING_ATTRIBUTES;
+    markdown_to_html_with(markdown, &MarkdownOptions::default())
+}
 
-    let parser = Parser::new_ext(markdown, options);
+/// Convert Markdown content to HTML, rewriting image and chapter link URLs
+/// and highlighting code blocks as configured.
+///
+/// Headings get the ids listed by [`extract_headings`]. TeX math is
+/// converted to MathML (see [`math`]).
+///
+/// # Arguments
+/// * `markdown` - The Markdown source
+/// * `options` - Rendering options
+///
+/// # Returns
+/// HTML string
+pub fn markdown_to_html_with(markdown: &str, options: &MarkdownOptions) -> String {
+    with_events(markdown, options, |events| {
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+        html_output
+    })
+}
 
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
+/// Marker line that starts a chapter's lazily loaded extended part.
+pub const EXTENDED_MARKER: &str = "<!-- extended -->";
 
-    html_output
+/// A chapter's HTML, split for lazy loading.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitHtml {
+    /// The part rendered with the page
+    pub main: String,
+    /// The rest, when the chapter is split
+    pub extended: Option<String>,
+    /// How many of the chapter's headings are in `main`; the rest are in
+    /// `extended`
+    pub main_headings: usize,
+}
+
+/// Convert Markdown content to HTML like [`markdown_to_html_with`], split
+/// into a main part and an extended part.
+///
+/// The extended part starts at the heading with id `extended_from` if
+/// given, otherwise at an [`EXTENDED_MARKER`] line (which is dropped). Only
+/// top-level headings and markers split the chapter; heading ids are
+/// assigned across both parts, as listed by [`extract_headings`].
+///
+/// # Arguments
+/// * `markdown` - The Markdown source
+/// * `options` - Rendering options
+/// * `extended_from` - Id of the heading starting the extended part
+pub fn markdown_to_split_html(
+    markdown: &str,
+    options: &MarkdownOptions,
+    extended_from: Option<&str>,
+) -> SplitHtml {
+    with_events(markdown, options, |mut events| {
+        let extended = extended_start(&events, extended_from).and_then(|(start, skip)| {
+            let rest = events.split_off(start);
+            let rest: Vec<_> = rest.into_iter().skip(skip).collect();
+            (!rest.is_empty()).then_some(rest)
+        });
+
+        let main_headings = events
+            .iter()
+            .filter(|event| matches!(event, Event::Start(Tag::Heading { .. })))
+            .count();
+        let mut main = String::new();
+        html::push_html(&mut main, events.into_iter());
+        SplitHtml {
+            main,
+            main_headings,
+            extended: extended.map(|events| {
+                let mut html_output = String::new();
+                html::push_html(&mut html_output, events.into_iter());
+                html_output
+            }),
+        }
+    })
+}
+
+/// Index of the first event of the extended part, and how many events
+/// (the marker's) to drop there.
+fn extended_start(events: &[Event<'_>], extended_from: Option<&str>) -> Option<(usize, usize)> {
+    let mut depth = 0usize;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Heading { id: Some(id), .. })
+                if depth == 0 && extended_from == Some(id.as_ref()) =>
+            {
+                return Some((i, 0));
+            }
+            Event::Start(Tag::HtmlBlock)
+                if depth == 0
+                    && extended_from.is_none()
+                    && matches!(events.get(i + 1), Some(Event::Html(html)) if html.trim() == EXTENDED_MARKER)
+                    && matches!(events.get(i + 2), Some(Event::End(TagEnd::HtmlBlock))) =>
+            {
+                return Some((i, 3));
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse Markdown into the HTML event stream and pass it to `f`.
+fn with_events<R>(
+    markdown: &str,
+    options: &MarkdownOptions,
+    f: impl FnOnce(Vec<Event<'_>>) -> R,
+) -> R {
+    let math = MathText::new(markdown, options.math);
+    let mut events = Vec::new();
+    // Open code block: its info string and text so far
+    let mut code_block: Option<(CowStr, String)> = None;
+
+    for event in Parser::new_ext(&math.markdown, PARSER_OPTIONS) {
+        match event {
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let dest_url = options
+                    .image_url
+                    .and_then(|image_url| image_url(&dest_url))
+                    .map_or(dest_url, Into::into);
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => match (
+                options.link_url.and_then(|link_url| link_url(&dest_url)),
+                options.htmx,
+            ) {
+                // The matching end event closes the tag with `</a>`
+                (Some(url), Some(config)) => {
+                    let url = escape_html(&url);
+                    let title = if title.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" title=\"{}\"", escape_html(&title))
+                    };
+                    let tag = format!(
+                        "<a href=\"{}\"{} {}>",
+                        url,
+                        title,
+                        content_link_attrs(&url, config)
+                    );
+                    events.push(Event::InlineHtml(CowStr::from(tag)));
+                }
+                (url, _) => events.push(Event::Start(Tag::Link {
+                    link_type,
+                    dest_url: url.map_or(dest_url, Into::into),
+                    title,
+                    id,
+                })),
+            },
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info,
+                    CodeBlockKind::Indented => CowStr::from(""),
+                };
+                code_block = Some((info, String::new()));
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((info, code)) = code_block.take() {
+                    let html = code::render_code_block(
+                        &code,
+                        &CodeInfo::parse(&info),
+                        options.highlighter,
+                    );
+                    events.push(Event::Html(CowStr::from(html)));
+                }
+            }
+            event => events.push(event),
+        }
+    }
+
+    let events = callout::convert_callouts(events);
+    f(math.render(add_heading_ids(events, &math, options.permalinks)))
+}
+
+/// Set the id of every heading (see [`HeadingIds`]) and optionally append a
+/// permalink anchor to it.
+///
+/// Ids are computed from the heading text with its formulas' TeX source.
+fn add_heading_ids<'e>(
+    events: Vec<Event<'e>>,
+    math: &MathText,
+    permalinks: bool,
+) -> Vec<Event<'e>> {
+    let mut ids = HeadingIds::for_events(&events);
+    let mut output = Vec::with_capacity(events.len());
+    // Index of the open heading's start event, and its text so far
+    let mut heading: Option<(usize, String)> = None;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                heading = Some((output.len(), String::new()));
+                output.push(event);
+            }
+            Event::Text(ref text) | Event::Code(ref text) if heading.is_some() => {
+                if let Some((_, heading_text)) = heading.as_mut() {
+                    heading_text.push_str(&math.restore(text));
+                }
+                output.push(event);
+            }
+            Event::End(TagEnd::Heading(_)) if heading.is_some() => {
+                let (start, text) = heading.take().unwrap();
+                let Event::Start(Tag::Heading { id, .. }) = &mut output[start] else {
+                    unreachable!("heading start index points at a heading");
+                };
+                let assigned = ids.assign(id.as_deref(), &text);
+                *id = Some(CowStr::from(assigned.clone()));
+
+                if permalinks {
+                    output.push(Event::InlineHtml(CowStr::from(format!(
+                        r##"<a class="permalink" href="#{}" aria-label="Link to this section">#</a>"##,
+                        escape_html(&assigned)
+                    ))));
+                }
+                output.push(event);
+            }
+            event => output.push(event),
+        }
+    }
+
+    output
+}
+
+/// Escape text for HTML content or a double-quoted attribute value.
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Resolve a link to another chapter's Markdown file to its routed URL.
+///
+/// Relative links ending in `.md` (e.g., `../guide/install.md#setup`) map to
+/// the same URL as the sidebar entry for that chapter, keeping the anchor.
+/// Returns None for other links.
+///
+/// # Arguments
+/// * `chapter_path` - Path of the linking chapter, relative to the source directory
+/// * `url` - The link destination as written in the Markdown
+pub fn resolve_chapter_link(chapter_path: &Path, url: &str) -> Option<String> {
+    let (target, anchor) = match url.split_once('#') {
+        Some((target, anchor)) => (target, Some(anchor)),
+        None => (url, None),
+    };
+    let file = target.split('?').next().unwrap_or(target);
+    if !file.ends_with(".md") {
+        return None;
+    }
+
+    let route = oob::path_to_url(&assets::resolve_relative_url(chapter_path, file)?);
+    Some(match anchor {
+        Some(anchor) => format!("{}#{}", route, anchor),
+        None => route,
+    })
+}
+
+/// Extract image URLs referenced in Markdown.
+///
+/// # Arguments
+/// * `markdown` - The Markdown source
+///
+/// # Returns
+/// Image destination URLs in document order
+pub fn extract_image_urls(markdown: &str) -> Vec<String> {
+    Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url.to_string()),
+            _ => None,
+        })
+        .collect()
 }
 
 /// Extract headings from Markdown for table of contents.
 ///
+/// Anchors are unique within the page: an explicit `{#id}` is kept, other
+/// headings use the slug of their text with `-1`, `-2`, ... added to repeats.
+/// With `math`, formulas in heading text are kept as their TeX source.
+///
 /// # Arguments
 /// * `markdown` - The Markdown source
+/// * `math` - Whether TeX math is enabled (as in [`MarkdownOptions::math`])
 ///
 /// # Returns
 /// Vector of (level, text, anchor) tuples
-pub fn extract_headings(markdown: &str) -> Vec<(u8, String, String)> {
-    use pulldown_cmark::{Event, HeadingLevel, Tag, TagEnd};
+pub fn extract_headings(markdown: &str, math: bool) -> Vec<(u8, String, String)> {
+    use pulldown_cmark::HeadingLevel;
 
-    let options = Options::ENABLE_HEADING_ATTRIBUTES;
-    let parser = Parser::new_ext(markdown, options);
+    let math = MathText::new(markdown, math);
+    let events: Vec<_> = Parser::new_ext(&math.markdown, PARSER_OPTIONS).collect();
 
+    let mut ids = HeadingIds::for_events(&events);
     let mut headings = Vec::new();
     let mut in_heading = false;
     let mut current_level = 0u8;
+    let mut current_id: Option<String> = None;
     let mut current_text = String::new();
 
-    for event in parser {
+    for event in events {
         match event {
-            Event::Start(Tag::Heading { level, .. }) => {
+            Event::Start(Tag::Heading { level, id, .. }) => {
                 in_heading = true;
+                current_id = id.map(|id| id.to_string());
                 current_level = match level {
                     HeadingLevel::H1 => 1,
                     HeadingLevel::H2 => 2,
@@ -70,17 +407,13 @@ pub fn extract_headings(markdown: &str) -> Vec<(u8, String, String)> {
                 };
                 current_text.clear();
             }
-            Event::End(TagEnd::Heading(_)) => {
-                if in_heading {
-                    let anchor = slugify(&current_text);
-                    headings.push((current_level, current_text.clone(), anchor));
-                    in_heading = false;
-                }
+            Event::End(TagEnd::Heading(_)) if in_heading => {
+                let anchor = ids.assign(current_id.as_deref(), &current_text);
+                headings.push((current_level, current_text.clone(), anchor));
+                in_heading = false;
             }
-            Event::Text(text) | Event::Code(text) => {
-                if in_heading {
-                    current_text.push_str(&text);
-                }
+            Event::Text(text) | Event::Code(text) if in_heading => {
+                current_text.push_str(&math.restore(&text));
             }
             _ => {}
         }
@@ -89,6 +422,52 @@ pub fn extract_headings(markdown: &str) -> Vec<(u8, String, String)> {
     headings
 }
 
+/// Assigns unique heading ids within a page.
+#[derive(Debug, Default)]
+pub struct HeadingIds {
+    used: HashSet<String>,
+}
+
+impl HeadingIds {
+    /// Ids for the headings among a page's events.
+    ///
+    /// Explicit `{#id}`s are reserved up front, so no generated id takes
+    /// one, even for a heading that comes before it.
+    pub fn for_events(events: &[Event]) -> Self {
+        let used = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+                _ => None,
+            })
+            .collect();
+        Self { used }
+    }
+
+    /// Id for the next heading: `explicit` if given, otherwise the slug of
+    /// `text`, suffixed with `-1`, `-2`, ... if already taken.
+    pub fn assign(&mut self, explicit: Option<&str>, text: &str) -> String {
+        if let Some(id) = explicit {
+            self.used.insert(id.to_string());
+            return id.to_string();
+        }
+
+        let mut base = slugify(text);
+        if base.is_empty() {
+            base = "section".to_string();
+        }
+
+        let mut id = base.clone();
+        let mut n = 0;
+        while self.used.contains(&id) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        self.used.insert(id.clone());
+        id
+    }
+}
+
 /// Convert text to a URL-safe slug.
 ///
 /// Uses GitHub-compatible slugification:
@@ -124,7 +503,7 @@ mod tests {
     fn test_markdown_to_html() {
         let md = "# Hello\n\nThis is **bold**.";
         let html = markdown_to_html(md);
-        assert!(html.contains("<h1>Hello</h1>"));
+        assert!(html.contains(r#"<h1 id="hello">Hello</h1>"#));
         assert!(html.contains("<strong>bold</strong>"));
     }
 
@@ -136,6 +515,161 @@ mod tests {
         assert!(html.contains("<td>1</td>"));
     }
 
+    #[test]
+    fn test_image_urls() {
+        let md = "![Logo](images/logo.png)\n\n![Remote](https://example.com/a.png)";
+        assert_eq!(
+            extract_image_urls(md),
+            vec!["images/logo.png", "https://example.com/a.png"]
+        );
+
+        let image_url = |url: &str| {
+            (url == "images/logo.png").then(|| "/assets/images/logo.1234abcd.png".to_string())
+        };
+        let options = MarkdownOptions {
+            image_url: Some(&image_url),
+            ..Default::default()
+        };
+        let html = markdown_to_html_with(md, &options);
+        assert!(html.contains(r#"src="/assets/images/logo.1234abcd.png""#));
+        assert!(html.contains(r#"src="https://example.com/a.png""#));
+    }
+
+    #[test]
+    fn test_resolve_chapter_link() {
+        let chapter = Path::new("guide/install.md");
+        let resolve = |url| resolve_chapter_link(chapter, url);
+
+        assert_eq!(resolve("usage.md").as_deref(), Some("/guide/usage"));
+        assert_eq!(
+            resolve("../intro.md#setup").as_deref(),
+            Some("/intro#setup")
+        );
+        assert_eq!(resolve("./README.md").as_deref(), Some("/guide/"));
+        assert_eq!(resolve("../README.md").as_deref(), Some("/"));
+        assert_eq!(resolve("#setup"), None);
+        assert_eq!(resolve("https://example.com/a.md"), None);
+        assert_eq!(resolve("../../outside.md"), None);
+        assert_eq!(resolve("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_chapter_links() {
+        let chapter = Path::new("guide/install.md");
+        let link_url = |url: &str| resolve_chapter_link(chapter, url);
+        let md = "[Usage](usage.md#setup \"Usage\") and [Site](https://example.com)";
+
+        let options = MarkdownOptions {
+            link_url: Some(&link_url),
+            ..Default::default()
+        };
+        let html = markdown_to_html_with(md, &options);
+        assert!(html.contains(r#"<a href="/guide/usage#setup" title="Usage">Usage</a>"#));
+        assert!(!html.contains("hx-get"));
+
+        let config = HtmxConfig::default();
+        let options = MarkdownOptions {
+            link_url: Some(&link_url),
+            htmx: Some(&config),
+            ..Default::default()
+        };
+        let html = markdown_to_html_with(md, &options);
+        assert!(html.contains(
+            r##"<a href="/guide/usage#setup" title="Usage" hx-get="/guide/usage" hx-target="#content""##
+        ));
+        assert!(html.contains(r##"hx-push-url="/guide/usage#setup">Usage</a>"##));
+        assert!(html.contains(r#"<a href="https://example.com">Site</a>"#));
+    }
+
+    #[test]
+    fn test_highlighted_code_blocks() {
+        let highlighter = Highlighter::new(&crate::config::HighlightConfig::default()).unwrap();
+        let options = MarkdownOptions {
+            highlighter: Some(&highlighter),
+            ..Default::default()
+        };
+        let md =
+            "```rust,ignore\nlet x = 1;\n```\n\n```\n<plain>\n```\n\n```nosuchlang\na < b\n```";
+        let html = markdown_to_html_with(md, &options);
+
+        assert!(html.contains(
+            r#"<pre class="hl-code"><code class="language-rust ignore"><span class="hl-source hl-rust">"#
+        ));
+        assert!(html.contains("<pre><code>&lt;plain&gt;\n</code></pre>"));
+        assert!(html.contains(r#"<pre><code class="language-nosuchlang">a &lt; b"#));
+
+        // Without a highlighter, mdBook attributes still become classes
+        assert!(markdown_to_html(md).contains(r#"<pre><code class="language-rust ignore">"#));
+    }
+
+    #[test]
+    fn test_heading_ids() {
+        let md = "# Setup\n\n## Setup\n\n## Setup\n\n## Custom {#my-id}\n\n## `run()` & more";
+        let html = markdown_to_html(md);
+        assert!(html.contains(r#"<h1 id="setup">Setup</h1>"#));
+        assert!(html.contains(r#"<h2 id="setup-1">Setup</h2>"#));
+        assert!(html.contains(r#"<h2 id="setup-2">Setup</h2>"#));
+        assert!(html.contains(r#"<h2 id="my-id">Custom</h2>"#));
+        assert!(html.contains(r#"<h2 id="run-more">"#));
+
+        let anchors: Vec<_> = extract_headings(md, false)
+            .into_iter()
+            .map(|h| h.2)
+            .collect();
+        assert_eq!(
+            anchors,
+            ["setup", "setup-1", "setup-2", "my-id", "run-more"]
+        );
+
+        // Explicit ids are reserved before earlier headings get theirs
+        let md = "## Setup\n\n## Install {#setup}";
+        let html = markdown_to_html(md);
+        assert!(html.contains(r#"<h2 id="setup-1">Setup</h2>"#));
+        assert!(html.contains(r#"<h2 id="setup">Install</h2>"#));
+        let anchors: Vec<_> = extract_headings(md, false)
+            .into_iter()
+            .map(|h| h.2)
+            .collect();
+        assert_eq!(anchors, ["setup-1", "setup"]);
+
+        let options = MarkdownOptions {
+            permalinks: true,
+            ..Default::default()
+        };
+        assert!(markdown_to_html_with("## Setup", &options).contains(
+            r##"<h2 id="setup">Setup<a class="permalink" href="#setup" aria-label="Link to this section">#</a></h2>"##
+        ));
+    }
+
+    #[test]
+    fn test_split_html() {
+        let md = "# Intro\n\nShort.\n\n<!-- extended -->\n\n## Details\n\nLong.\n\n## Intro";
+        let split = markdown_to_split_html(md, &MarkdownOptions::default(), None);
+        assert_eq!(split.main, "<h1 id=\"intro\">Intro</h1>\n<p>Short.</p>\n");
+        assert_eq!(split.main_headings, 1);
+        assert_eq!(
+            split.extended.as_deref(),
+            Some("<h2 id=\"details\">Details</h2>\n<p>Long.</p>\n<h2 id=\"intro-1\">Intro</h2>\n")
+        );
+
+        let split = markdown_to_split_html(md, &MarkdownOptions::default(), Some("intro-1"));
+        assert!(split.main.contains("<!-- extended -->"));
+        assert_eq!(
+            split.extended.as_deref(),
+            Some("<h2 id=\"intro-1\">Intro</h2>\n")
+        );
+
+        // Nested markers, missing headings and trailing markers do not split
+        for (md, from) in [
+            ("> <!-- extended -->\n>\n> text", None),
+            ("# Intro", Some("missing")),
+            ("Text\n\n<!-- extended -->\n", None),
+        ] {
+            let split = markdown_to_split_html(md, &MarkdownOptions::default(), from);
+            assert_eq!(split.extended, None, "{}", md);
+        }
+    }
+
     #[test]
     fn test_slugify() {
         assert_eq!(slugify("Hello World"), "hello-world");
@@ -146,7 +680,7 @@ mod tests {
     #[test]
     fn test_extract_headings() {
         let md = "# Title\n\nContent\n\n## Section 1\n\nMore content\n\n### Subsection";
-        let headings = extract_headings(md);
+        let headings = extract_headings(md, false);
 
         assert_eq!(headings.len(), 3);
         assert_eq!(headings[0], (1, "Title".to_string(), "title".to_string()));
@@ -158,5 +692,11 @@ mod tests {
             headings[2],
             (3, "Subsection".to_string(), "subsection".to_string())
         );
+
+        // Footnote references are not part of the heading text
+        let md = "## Setup[^1] ~~old~~\n\n[^1]: A note.";
+        let headings = extract_headings(md, false);
+        assert_eq!(headings[0].1, "Setup old");
+        assert!(markdown_to_html(md).contains(&format!(r#"<h2 id="{}">"#, headings[0].2)));
     }
 }
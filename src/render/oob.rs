@@ -1,13 +1,16 @@
 //! Out-of-Band (OOB) swap generation.
 //!
-//! Generates OOB updates for sidebar and breadcrumbs that are appended
-//! to fragment responses. This enables updating multiple parts of the
-//! page with a single HTMX request.
+//! Generates OOB updates for the sidebar, breadcrumbs and page table of
+//! contents that are appended to fragment responses. This enables updating
+//! multiple parts of the page with a single HTMX request.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
 
 use serde::Serialize;
 use tera::{Context, Tera};
 
-use crate::context::Chapter;
+use crate::context::{BookItem, Chapter};
 
 /// Breadcrumb entry for navigation trail.
 #[derive(Debug, Clone, Serialize)]
@@ -18,6 +21,8 @@ pub struct Breadcrumb {
     pub path: String,
     /// Whether this is the current page
     pub is_current: bool,
+    /// Whether this crumb links to its page (false for draft and unnumbered chapters)
+    pub is_link: bool,
 }
 
 /// Navigation item for sidebar.
@@ -37,6 +42,19 @@ pub struct NavItem {
     pub number: Option<String>,
 }
 
+/// Entry in a page's table of contents.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TocEntry {
+    /// Heading text
+    pub title: String,
+    /// Heading id (without `#`)
+    pub anchor: String,
+    /// Heading level (2-6)
+    pub level: u8,
+    /// Nested headings
+    pub children: Vec<TocEntry>,
+}
+
 /// Context for rendering OOB sidebar.
 #[derive(Debug, Clone, Serialize)]
 pub struct SidebarContext {
@@ -60,6 +78,8 @@ pub struct OobUpdates {
     pub sidebar: Option<String>,
     /// Breadcrumb OOB HTML
     pub breadcrumb: Option<String>,
+    /// Table of contents OOB HTML
+    pub toc: Option<String>,
 }
 
 impl OobUpdates {
@@ -75,12 +95,16 @@ impl OobUpdates {
             html.push_str(breadcrumb);
         }
 
+        if let Some(ref toc) = self.toc {
+            html.push_str(toc);
+        }
+
         html
     }
 
     /// Check if there are any OOB updates.
     pub fn is_empty(&self) -> bool {
-        self.sidebar.is_none() && self.breadcrumb.is_none()
+        self.sidebar.is_none() && self.breadcrumb.is_none() && self.toc.is_none()
     }
 }
 
@@ -89,21 +113,25 @@ impl OobUpdates {
 /// # Arguments
 /// * `tera` - Template engine
 /// * `chapter` - Current chapter being rendered
-/// * `all_chapters` - All chapters for sidebar navigation
-/// * `active_path` - URL path of current page
+/// * `sections` - Top-level book items for sidebar navigation
+/// * `base_url` - Site base URL, ending in `/`
+/// * `active_path` - URL of current page, including the base URL
+/// * `hidden` - Source paths of chapters hidden from navigation
 ///
 /// # Returns
 /// OOB updates to append to fragment response
 pub fn render_oob_updates(
     tera: &Tera,
     chapter: &Chapter,
-    all_chapters: &[&Chapter],
+    sections: &[BookItem],
+    base_url: &str,
     active_path: &str,
+    hidden: &HashSet<PathBuf>,
 ) -> anyhow::Result<OobUpdates> {
     let mut updates = OobUpdates::default();
 
     // Build sidebar context
-    let sidebar_items = build_nav_items(all_chapters, active_path);
+    let sidebar_items = build_nav_items(sections, base_url, active_path, hidden);
     let sidebar_ctx = SidebarContext {
         items: sidebar_items,
         active_path: active_path.to_string(),
@@ -122,7 +150,7 @@ pub fn render_oob_updates(
     }
 
     // Build breadcrumb context
-    let crumbs = build_breadcrumbs(chapter, active_path);
+    let crumbs = build_breadcrumbs(chapter, sections, base_url, active_path);
     let breadcrumb_ctx = BreadcrumbContext { crumbs };
 
     // Render breadcrumb OOB
@@ -138,70 +166,207 @@ pub fn render_oob_updates(
     Ok(updates)
 }
 
-/// Build navigation items from chapters.
-fn build_nav_items(chapters: &[&Chapter], active_path: &str) -> Vec<NavItem> {
-    chapters
-        .iter()
-        .filter_map(|ch| {
-            ch.path.as_ref().map(|path| {
-                let url_path = path_to_url(path);
-                let is_active = url_path == active_path;
-                let is_expanded = is_active || active_path.starts_with(&url_path);
-
-                NavItem {
-                    title: ch.name.clone(),
-                    path: url_path,
-                    is_active,
-                    children: vec![], // TODO: Handle nested chapters
-                    is_expanded,
-                    number: ch.number.as_ref().map(|nums| {
-                        nums.iter()
-                            .map(|n| n.to_string())
-                            .collect::<Vec<_>>()
-                            .join(".")
-                    }),
-                }
-            })
-        })
-        .collect()
+/// Render the "On this page" panel as an OOB swap.
+///
+/// An empty `toc` still produces an update, which clears the panel left
+/// over from the previous page.
+pub fn render_toc_oob(tera: &Tera, toc: &[TocEntry]) -> Option<String> {
+    let mut ctx = Context::new();
+    ctx.insert("toc", toc);
+
+    tera.render("partials/toc.html", &ctx).ok().map(|toc_html| {
+        format!(
+            r#"<nav id="page-toc" class="page-toc" aria-label="On this page" hx-swap-oob="true">{}</nav>"#,
+            toc_html
+        )
+    })
+}
+
+/// Build a nested table of contents from a page's headings.
+///
+/// Covers heading levels 2 to `depth`; the level-1 heading is the page
+/// title. Each heading is nested under the closest preceding heading of a
+/// lower level.
+///
+/// # Arguments
+/// * `headings` - (level, text, anchor) tuples from [`super::extract_headings`]
+/// * `depth` - Deepest heading level to include
+pub fn build_toc(headings: &[(u8, String, String)], depth: u8) -> Vec<TocEntry> {
+    fn insert(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+        match entries.last_mut() {
+            Some(last) if last.level < entry.level => insert(&mut last.children, entry),
+            _ => entries.push(entry),
+        }
+    }
+
+    let mut toc = Vec::new();
+    for (level, text, anchor) in headings {
+        if (2..=depth).contains(level) {
+            insert(
+                &mut toc,
+                TocEntry {
+                    title: text.clone(),
+                    anchor: anchor.clone(),
+                    level: *level,
+                    children: Vec::new(),
+                },
+            );
+        }
+    }
+    toc
+}
+
+/// Build the navigation tree from book sections.
+///
+/// Nested chapters become `children` of their parent. Every ancestor of the
+/// active page is marked as expanded. Draft chapters are kept (with an empty
+/// path) so their sub-chapters stay in place. Hidden chapters are left out,
+/// and their sub-chapters take their place in the tree. Item paths are
+/// prefixed with `base_url`, so `active_path` must include it too.
+pub fn build_nav_items(
+    sections: &[BookItem],
+    base_url: &str,
+    active_path: &str,
+    hidden: &HashSet<PathBuf>,
+) -> Vec<NavItem> {
+    let mut items = Vec::new();
+
+    for item in sections {
+        let BookItem::Chapter(ch) = item else {
+            continue;
+        };
+
+        if ch.path.as_ref().is_some_and(|p| hidden.contains(p)) {
+            items.extend(build_nav_items(
+                &ch.sub_items,
+                base_url,
+                active_path,
+                hidden,
+            ));
+        } else {
+            items.push(build_nav_item(ch, base_url, active_path, hidden));
+        }
+    }
+
+    items
+}
+
+/// Build a single navigation item and its children.
+fn build_nav_item(
+    ch: &Chapter,
+    base_url: &str,
+    active_path: &str,
+    hidden: &HashSet<PathBuf>,
+) -> NavItem {
+    let url_path = ch
+        .path
+        .as_deref()
+        .map(|p| url_with_base(base_url, &path_to_url(p)))
+        .unwrap_or_default();
+    let is_active = !url_path.is_empty() && url_path == active_path;
+    let children = build_nav_items(&ch.sub_items, base_url, active_path, hidden);
+    let is_expanded = is_active || children.iter().any(|c| c.is_active || c.is_expanded);
+
+    NavItem {
+        title: ch.name.clone(),
+        path: url_path,
+        is_active,
+        children,
+        is_expanded,
+        number: ch.number.as_ref().map(|nums| {
+            nums.iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        }),
+    }
 }
 
 /// Build breadcrumb trail for a chapter.
-fn build_breadcrumbs(chapter: &Chapter, active_path: &str) -> Vec<Breadcrumb> {
+///
+/// Parent crumbs come from the chapter's actual ancestors in the book tree,
+/// so their URLs match the pages the ancestors render to. The Home crumb
+/// links to `base_url`, and `active_path` must include it.
+pub fn build_breadcrumbs(
+    chapter: &Chapter,
+    sections: &[BookItem],
+    base_url: &str,
+    active_path: &str,
+) -> Vec<Breadcrumb> {
     let mut crumbs = Vec::new();
 
     // Home crumb
     crumbs.push(Breadcrumb {
         title: "Home".to_string(),
-        path: "/".to_string(),
-        is_current: active_path == "/",
+        path: base_url.to_string(),
+        is_current: active_path == base_url,
+        is_link: true,
     });
 
-    // Parent crumbs from parent_names
-    for parent in chapter.parent_names.iter() {
-        // Build path from parent structure
-        // This is simplified - in practice, we'd need to track parent paths
+    // Parent crumbs from the chapter's ancestors
+    let mut ancestors = Vec::new();
+    find_ancestors(sections, chapter, &mut ancestors);
+    for parent in ancestors {
+        let path = parent
+            .path
+            .as_deref()
+            .map(|p| url_with_base(base_url, &path_to_url(p)));
+
+        // Skip the root page, which is already the Home crumb
+        if path.as_deref() == Some(base_url) {
+            continue;
+        }
+
         crumbs.push(Breadcrumb {
-            title: parent.clone(),
-            path: format!("/{}", parent.to_lowercase().replace(' ', "-")),
+            title: parent.name.clone(),
+            is_link: path.is_some() && parent.number.is_some(),
+            path: path.unwrap_or_default(),
             is_current: false,
         });
     }
 
     // Current page crumb
-    if active_path != "/" {
+    if active_path != base_url {
         crumbs.push(Breadcrumb {
             title: chapter.name.clone(),
             path: active_path.to_string(),
             is_current: true,
+            is_link: true,
         });
     }
 
     crumbs
 }
 
+/// Collect the ancestor chapters of `target`, outermost first.
+///
+/// Returns true if `target` was found within `sections`.
+fn find_ancestors<'a>(
+    sections: &'a [BookItem],
+    target: &Chapter,
+    ancestors: &mut Vec<&'a Chapter>,
+) -> bool {
+    for item in sections {
+        let BookItem::Chapter(ch) = item else {
+            continue;
+        };
+
+        if std::ptr::eq(ch, target) || (ch.path.is_some() && ch.path == target.path) {
+            return true;
+        }
+
+        ancestors.push(ch);
+        if find_ancestors(&ch.sub_items, target, ancestors) {
+            return true;
+        }
+        ancestors.pop();
+    }
+
+    false
+}
+
 /// Convert file path to URL path.
-fn path_to_url(path: &std::path::Path) -> String {
+pub(crate) fn path_to_url(path: &std::path::Path) -> String {
     let path_str = path.with_extension("").to_string_lossy().to_string();
 
     if path_str == "README" || path_str.ends_with("/README") {
@@ -215,6 +380,16 @@ fn path_to_url(path: &std::path::Path) -> String {
     }
 }
 
+/// Prefix a root-relative route with the site's base URL.
+///
+/// `base_url` ends in `/` (see [`crate::config::HtmxConfig::base_url`]), so
+/// `/guide/install` under `/docs/` becomes `/docs/guide/install`. Every
+/// link the browser follows goes through here; manifest keys and search
+/// URLs stay root-relative.
+pub fn url_with_base(base_url: &str, route: &str) -> String {
+    format!("{}{}", base_url, route.trim_start_matches('/'))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,8 +406,9 @@ mod tests {
             sub_items: vec![],
             parent_names: vec![],
         };
+        let sections = vec![BookItem::Chapter(chapter.clone())];
 
-        let crumbs = build_breadcrumbs(&chapter, "/");
+        let crumbs = build_breadcrumbs(&chapter, &sections, "/", "/");
         assert_eq!(crumbs.len(), 1);
         assert!(crumbs[0].is_current);
     }
@@ -242,44 +418,78 @@ mod tests {
         let chapter = Chapter {
             name: "Installation".to_string(),
             content: String::new(),
-            path: Some(PathBuf::from("guide/installation.md")),
+            path: Some(PathBuf::from("getting-started/installation.md")),
             source_path: None,
             number: Some(vec![1, 2]),
             sub_items: vec![],
-            parent_names: vec!["Guide".to_string()],
+            parent_names: vec!["User Guide".to_string()],
         };
-
-        let crumbs = build_breadcrumbs(&chapter, "/guide/installation");
-        assert_eq!(crumbs.len(), 3); // Home > Guide > Installation
+        let sections = vec![BookItem::Chapter(nav_chapter(
+            "User Guide",
+            "getting-started/README.md",
+            vec![1],
+            vec![BookItem::Chapter(chapter.clone())],
+        ))];
+
+        let crumbs = build_breadcrumbs(&chapter, &sections, "/", "/getting-started/installation");
+        assert_eq!(crumbs.len(), 3); // Home > User Guide > Installation
         assert!(!crumbs[0].is_current); // Home
-        assert!(!crumbs[1].is_current); // Guide
+        assert!(!crumbs[1].is_current); // User Guide
         assert!(crumbs[2].is_current); // Installation
+
+        // Parent URL comes from the parent's file path, not its title
+        assert_eq!(crumbs[1].title, "User Guide");
+        assert_eq!(crumbs[1].path, "/getting-started/");
+        assert!(crumbs[1].is_link);
     }
 
     #[test]
-    fn test_nav_items() {
-        let ch1 = Chapter {
-            name: "Introduction".to_string(),
+    fn test_breadcrumb_draft_and_unnumbered_ancestors() {
+        let chapter = nav_chapter("Leaf", "section/leaf.md", vec![1, 1, 1], vec![]);
+        let draft = Chapter {
+            name: "Draft".to_string(),
             content: String::new(),
-            path: Some(PathBuf::from("intro.md")),
+            path: None,
             source_path: None,
-            number: Some(vec![1]),
-            sub_items: vec![],
+            number: Some(vec![1, 1]),
+            sub_items: vec![BookItem::Chapter(chapter.clone())],
             parent_names: vec![],
         };
+        let mut unnumbered = nav_chapter("Section", "section/README.md", vec![], vec![]);
+        unnumbered.number = None;
+        unnumbered.sub_items = vec![BookItem::Chapter(draft)];
+        let sections = vec![BookItem::Chapter(unnumbered)];
+
+        let crumbs = build_breadcrumbs(&chapter, &sections, "/", "/section/leaf");
+        assert_eq!(crumbs.len(), 4);
+        assert_eq!(crumbs[1].title, "Section");
+        assert!(!crumbs[1].is_link);
+        assert_eq!(crumbs[2].title, "Draft");
+        assert!(!crumbs[2].is_link);
+        assert!(crumbs[3].is_current);
+    }
 
-        let ch2 = Chapter {
-            name: "Guide".to_string(),
+    fn nav_chapter(name: &str, path: &str, number: Vec<u32>, sub_items: Vec<BookItem>) -> Chapter {
+        Chapter {
+            name: name.to_string(),
             content: String::new(),
-            path: Some(PathBuf::from("guide.md")),
+            path: Some(PathBuf::from(path)),
             source_path: None,
-            number: Some(vec![2]),
-            sub_items: vec![],
+            number: Some(number),
+            sub_items,
             parent_names: vec![],
-        };
+        }
+    }
 
-        let chapters: Vec<&Chapter> = vec![&ch1, &ch2];
-        let items = build_nav_items(&chapters, "/intro");
+    #[test]
+    fn test_nav_items() {
+        let sections = vec![
+            BookItem::Chapter(nav_chapter("Introduction", "intro.md", vec![1], vec![])),
+            BookItem::Separator,
+            BookItem::Chapter(nav_chapter("Guide", "guide.md", vec![2], vec![])),
+        ];
+
+        let items = build_nav_items(&sections, "/", "/intro", &HashSet::new());
 
         assert_eq!(items.len(), 2);
         assert!(items[0].is_active);
@@ -287,11 +497,158 @@ mod tests {
         assert_eq!(items[0].number, Some("1".to_string()));
     }
 
+    #[test]
+    fn test_nav_items_nested() {
+        let sections = vec![
+            BookItem::Chapter(nav_chapter(
+                "Guide",
+                "guide/README.md",
+                vec![1],
+                vec![BookItem::Chapter(nav_chapter(
+                    "Setup",
+                    "guide/setup.md",
+                    vec![1, 1],
+                    vec![BookItem::Chapter(nav_chapter(
+                        "Linux",
+                        "guide/setup/linux.md",
+                        vec![1, 1, 1],
+                        vec![],
+                    ))],
+                ))],
+            )),
+            BookItem::Chapter(nav_chapter(
+                "Reference",
+                "reference.md",
+                vec![2],
+                vec![BookItem::Chapter(nav_chapter(
+                    "Config",
+                    "reference/config.md",
+                    vec![2, 1],
+                    vec![],
+                ))],
+            )),
+        ];
+
+        let items = build_nav_items(&sections, "/", "/guide/setup/linux", &HashSet::new());
+
+        assert_eq!(items.len(), 2);
+        let guide = &items[0];
+        assert_eq!(guide.children.len(), 1);
+        assert!(guide.is_expanded);
+        assert!(!guide.is_active);
+
+        let setup = &guide.children[0];
+        assert!(setup.is_expanded);
+        assert_eq!(setup.number, Some("1.1".to_string()));
+
+        let linux = &setup.children[0];
+        assert!(linux.is_active);
+        assert!(linux.is_expanded);
+
+        // Sibling sections stay collapsed
+        assert!(!items[1].is_expanded);
+        assert!(!items[1].children[0].is_active);
+    }
+
+    #[test]
+    fn test_nav_items_hidden() {
+        let sections = vec![
+            BookItem::Chapter(nav_chapter("Intro", "intro.md", vec![1], vec![])),
+            BookItem::Chapter(nav_chapter(
+                "Internal",
+                "internal.md",
+                vec![2],
+                vec![BookItem::Chapter(nav_chapter(
+                    "Visible Child",
+                    "internal/child.md",
+                    vec![2, 1],
+                    vec![],
+                ))],
+            )),
+        ];
+        let hidden: HashSet<PathBuf> = [PathBuf::from("internal.md")].into_iter().collect();
+
+        let items = build_nav_items(&sections, "/", "/internal", &hidden);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Intro");
+        assert_eq!(items[1].title, "Visible Child");
+        assert!(items.iter().all(|item| !item.is_active));
+    }
+
+    #[test]
+    fn test_sidebar_oob_renders_nested_items() {
+        let tera = crate::templates::init_templates(None).unwrap();
+        let child = nav_chapter("Setup", "guide/setup.md", vec![1, 1], vec![]);
+        let sections = vec![BookItem::Chapter(nav_chapter(
+            "Guide",
+            "guide/README.md",
+            vec![1],
+            vec![BookItem::Chapter(child.clone())],
+        ))];
+
+        let updates = render_oob_updates(
+            &tera,
+            &child,
+            &sections,
+            "/",
+            "/guide/setup",
+            &HashSet::new(),
+        )
+        .unwrap();
+        let sidebar = updates.sidebar.unwrap();
+        assert!(sidebar.contains("nav-children"));
+        assert!(sidebar.contains("aria-current=\"page\""));
+    }
+
+    #[test]
+    fn test_build_toc() {
+        let heading = |level: u8, text: &str| (level, text.to_string(), text.to_lowercase());
+        let headings = vec![
+            heading(1, "Title"),
+            heading(2, "Install"),
+            heading(3, "Linux"),
+            heading(4, "Arch"),
+            heading(3, "macOS"),
+            heading(2, "Usage"),
+        ];
+
+        let toc = build_toc(&headings, 3);
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].title, "Install");
+        let children: Vec<_> = toc[0].children.iter().map(|e| e.anchor.as_str()).collect();
+        assert_eq!(children, ["linux", "macos"]);
+        assert!(toc[0].children[0].children.is_empty());
+        assert_eq!(toc[1].title, "Usage");
+
+        assert_eq!(
+            build_toc(&headings, 4)[0].children[0].children[0].title,
+            "Arch"
+        );
+        assert!(build_toc(&headings, 1).is_empty());
+    }
+
+    #[test]
+    fn test_toc_oob() {
+        let tera = crate::templates::init_templates(None).unwrap();
+        let toc = build_toc(&[(2, "Install".to_string(), "install".to_string())], 3);
+
+        let html = render_toc_oob(&tera, &toc).unwrap();
+        assert!(html.contains(r#"id="page-toc""#));
+        assert!(html.contains(r#"hx-swap-oob="true""#));
+        assert!(html.contains("On this page"));
+        assert!(html.contains(r##"href="#install""##));
+
+        let empty = render_toc_oob(&tera, &[]).unwrap();
+        assert!(!empty.contains("page-toc-title"));
+    }
+
     #[test]
     fn test_oob_updates_to_html() {
         let updates = OobUpdates {
             sidebar: Some("<ul>sidebar</ul>".to_string()),
             breadcrumb: Some("<ol>crumbs</ol>".to_string()),
+            toc: None,
         };
 
         let html = updates.to_html();
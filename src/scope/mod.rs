@@ -0,0 +1,154 @@
+//! Audience scopes published as separate output trees.
+//!
+//! Each scope declared in `[output.htmx.scopes]` is rendered into
+//! `scopes/<name>/` from a copy of the book with out-of-scope chapters
+//! removed, so its pages, sidebar, prev/next links, manifest and search
+//! index never mention them.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use crate::config::ScopeConfig;
+use crate::context::BookItem;
+use crate::frontmatter;
+
+/// Directory (relative to the output directory) holding the scope trees.
+pub const SCOPES_DIR: &str = "scopes";
+
+/// Decides which chapters belong in a scope's output tree.
+#[derive(Debug, Clone)]
+pub struct ScopeFilter<'a> {
+    /// The scope itself and the scopes it includes
+    visible: HashSet<&'a str>,
+    /// Scope assumed for chapters without one
+    default_scope: Option<&'a str>,
+}
+
+impl<'a> ScopeFilter<'a> {
+    /// Create the filter for scope `name`.
+    pub fn new(name: &'a str, config: &'a ScopeConfig, default_scope: Option<&'a str>) -> Self {
+        let mut visible: HashSet<&str> = config.includes.iter().map(String::as_str).collect();
+        visible.insert(name);
+        Self {
+            visible,
+            default_scope,
+        }
+    }
+
+    /// Whether a page with the given frontmatter scope is visible.
+    ///
+    /// Pages without a scope (and no `default-scope`) are visible everywhere.
+    pub fn is_visible(&self, scope: Option<&str>) -> bool {
+        scope
+            .or(self.default_scope)
+            .is_none_or(|scope| self.visible.contains(scope))
+    }
+
+    /// Copy the book tree without the chapters outside this scope.
+    ///
+    /// A chapter that is filtered out takes its sub-chapters with it, so
+    /// nested pages never appear without their parent.
+    pub fn filter_items(&self, items: &[BookItem]) -> Result<Vec<BookItem>> {
+        let mut filtered = Vec::with_capacity(items.len());
+
+        for item in items {
+            match item {
+                BookItem::Chapter(chapter) => {
+                    if let Some(ref path) = chapter.path {
+                        let (frontmatter, _) =
+                            frontmatter::parse_frontmatter(&chapter.content, path)?;
+                        if !self.is_visible(frontmatter.scope.as_deref()) {
+                            continue;
+                        }
+                    }
+
+                    let mut chapter = chapter.clone();
+                    chapter.sub_items = self.filter_items(&chapter.sub_items)?;
+                    filtered.push(BookItem::Chapter(chapter));
+                }
+                other => filtered.push(other.clone()),
+            }
+        }
+
+        Ok(filtered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Chapter;
+    use std::path::PathBuf;
+
+    fn chapter(name: &str, scope: Option<&str>, sub_items: Vec<BookItem>) -> BookItem {
+        let content = match scope {
+            Some(scope) => format!("---\nscope: {}\n---\n# {}", scope, name),
+            None => format!("# {}", name),
+        };
+        BookItem::Chapter(Chapter {
+            name: name.to_string(),
+            content,
+            path: Some(PathBuf::from(format!("{}.md", name))),
+            source_path: Some(PathBuf::from(format!("{}.md", name))),
+            number: None,
+            sub_items,
+            parent_names: vec![],
+        })
+    }
+
+    fn names(items: &[BookItem]) -> Vec<String> {
+        let mut out = Vec::new();
+        for item in items {
+            if let BookItem::Chapter(ch) = item {
+                out.push(ch.name.clone());
+                out.extend(names(&ch.sub_items));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_filter_items() {
+        let book = vec![
+            chapter("intro", None, vec![]),
+            chapter(
+                "guide",
+                Some("public"),
+                vec![chapter("partner-api", Some("partner"), vec![])],
+            ),
+            chapter(
+                "runbooks",
+                Some("internal"),
+                vec![chapter("oncall", None, vec![])],
+            ),
+        ];
+
+        let public = ScopeConfig::default();
+        let filter = ScopeFilter::new("public", &public, None);
+        assert_eq!(
+            names(&filter.filter_items(&book).unwrap()),
+            ["intro", "guide"]
+        );
+
+        let partner = ScopeConfig {
+            includes: vec!["public".to_string()],
+        };
+        let filter = ScopeFilter::new("partner", &partner, None);
+        assert_eq!(
+            names(&filter.filter_items(&book).unwrap()),
+            ["intro", "guide", "partner-api"]
+        );
+    }
+
+    #[test]
+    fn test_default_scope() {
+        let config = ScopeConfig::default();
+        let filter = ScopeFilter::new("partner", &config, Some("internal"));
+        assert!(!filter.is_visible(None));
+        assert!(filter.is_visible(Some("partner")));
+
+        let filter = ScopeFilter::new("internal", &config, Some("internal"));
+        assert!(filter.is_visible(None));
+    }
+}
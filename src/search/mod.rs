@@ -2,12 +2,20 @@
 //!
 //! Generates search-index.json for client-side or server-side search.
 //! See ADR-0005 and ADR-0021 for design decisions.
+//!
+//! With the `sqlite-search` feature the index can also be written to a
+//! SQLite FTS5 database (search.db).
+
+#[cfg(feature = "sqlite-search")]
+mod sqlite;
 
 use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 use serde::{Deserialize, Serialize};
 
 use crate::config::SearchConfig;
 use crate::frontmatter::Frontmatter;
+use crate::render::callout::strip_markers;
+use crate::render::MathText;
 
 /// The search index containing all searchable content.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,14 +121,12 @@ impl SearchIndex {
         serde_json::to_string_pretty(self)
     }
 
-    /// Filter index by scope.
-    ///
-    /// Returns a new index containing only documents matching the given scope.
-    pub fn filter_by_scope(&self, scope: &str) -> Self {
+    /// Return a new index with only the documents matching `keep`.
+    pub fn filter(&self, keep: impl Fn(&SearchDocument) -> bool) -> Self {
         let documents: Vec<_> = self
             .documents
             .iter()
-            .filter(|doc| doc.scope.as_deref() == Some(scope) || doc.scope.is_none())
+            .filter(|doc| keep(doc))
             .cloned()
             .collect();
 
@@ -132,40 +138,35 @@ impl SearchIndex {
         }
     }
 
+    /// Filter index by scope.
+    ///
+    /// Returns a new index containing only documents matching the given scope.
+    pub fn filter_by_scope(&self, scope: &str) -> Self {
+        self.filter(|doc| doc.scope.as_deref() == Some(scope) || doc.scope.is_none())
+    }
+
     /// Filter index by authentication level.
     ///
     /// Returns a new index containing only documents accessible at the given level.
     pub fn filter_by_auth(&self, level: &str) -> Self {
-        let documents: Vec<_> = self
-            .documents
-            .iter()
-            .filter(|doc| {
-                match &doc.auth {
-                    None => true, // No auth requirement
-                    Some(auth) => {
-                        match &auth.authn {
-                            None => true,
-                            Some(required) => {
-                                // "public" is accessible to all
-                                // "authenticated" requires authentication
-                                // "verified" requires verified authentication
-                                required == "public"
-                                    || (level == "authenticated" && required != "verified")
-                                    || level == "verified"
-                            }
+        self.filter(|doc| {
+            match &doc.auth {
+                None => true, // No auth requirement
+                Some(auth) => {
+                    match &auth.authn {
+                        None => true,
+                        Some(required) => {
+                            // "public" is accessible to all
+                            // "authenticated" requires authentication
+                            // "verified" requires verified authentication
+                            required == "public"
+                                || (level == "authenticated" && required != "verified")
+                                || level == "verified"
                         }
                     }
                 }
-            })
-            .cloned()
-            .collect();
-
-        Self {
-            schema: self.schema.clone(),
-            version: self.version.clone(),
-            config: self.config.clone(),
-            documents,
-        }
+            }
+        })
     }
 }
 
@@ -178,12 +179,23 @@ impl Default for SearchIndex {
 /// Builder for creating search documents from chapter content.
 pub struct SearchDocumentBuilder<'a> {
     config: &'a SearchConfig,
+    math: bool,
 }
 
 impl<'a> SearchDocumentBuilder<'a> {
     /// Create a new document builder with the given configuration.
     pub fn new(config: &'a SearchConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            math: false,
+        }
+    }
+
+    /// Treat `$...$` and `$$...$$` as TeX math, as the renderer does when
+    /// `[output.htmx.math]` is enabled.
+    pub fn with_math(mut self, enabled: bool) -> Self {
+        self.math = enabled;
+        self
     }
 
     /// Build a search document from chapter content.
@@ -195,7 +207,8 @@ impl<'a> SearchDocumentBuilder<'a> {
     /// * `frontmatter` - Parsed frontmatter
     ///
     /// # Returns
-    /// A `SearchDocument` if the page should be indexed, None if `no_search` is set.
+    /// A `SearchDocument` if the page should be indexed, None if `no_search` or
+    /// `hidden` is set.
     pub fn build(
         &self,
         path: String,
@@ -203,8 +216,8 @@ impl<'a> SearchDocumentBuilder<'a> {
         content: &str,
         frontmatter: &Frontmatter,
     ) -> Option<SearchDocument> {
-        // Skip if no_search is set
-        if frontmatter.no_search {
+        // Skip if no_search or hidden is set
+        if frontmatter.no_search || frontmatter.hidden {
             return None;
         }
 
@@ -213,7 +226,7 @@ impl<'a> SearchDocumentBuilder<'a> {
 
         // Extract body text if configured
         let body = if self.config.index_content {
-            let text = strip_markdown(content);
+            let text = strip_markdown(content, self.math);
             let text = if let Some(max_len) = self.config.max_excerpt_length {
                 truncate_text(&text, max_len)
             } else {
@@ -249,52 +262,19 @@ impl<'a> SearchDocumentBuilder<'a> {
     }
 
     /// Extract headings from markdown up to the configured level.
+    ///
+    /// Anchors are the ids [`crate::render::markdown_to_html`] gives the
+    /// headings, so search results link to the right section.
     fn extract_headings(&self, markdown: &str) -> Vec<HeadingEntry> {
-        use pulldown_cmark::HeadingLevel;
-
-        let options = Options::ENABLE_HEADING_ATTRIBUTES;
-        let parser = Parser::new_ext(markdown, options);
-
-        let mut headings = Vec::new();
-        let mut in_heading = false;
-        let mut current_level = 0u8;
-        let mut current_text = String::new();
-
-        for event in parser {
-            match event {
-                Event::Start(Tag::Heading { level, .. }) => {
-                    in_heading = true;
-                    current_level = match level {
-                        HeadingLevel::H1 => 1,
-                        HeadingLevel::H2 => 2,
-                        HeadingLevel::H3 => 3,
-                        HeadingLevel::H4 => 4,
-                        HeadingLevel::H5 => 5,
-                        HeadingLevel::H6 => 6,
-                    };
-                    current_text.clear();
-                }
-                Event::End(TagEnd::Heading(_)) => {
-                    if in_heading && current_level <= self.config.heading_split_level {
-                        let anchor = format!("#{}", slugify(&current_text));
-                        headings.push(HeadingEntry {
-                            level: current_level,
-                            text: current_text.clone(),
-                            anchor,
-                        });
-                    }
-                    in_heading = false;
-                }
-                Event::Text(text) | Event::Code(text) => {
-                    if in_heading {
-                        current_text.push_str(&text);
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        headings
+        crate::render::extract_headings(markdown, self.math)
+            .into_iter()
+            .filter(|(level, _, _)| *level <= self.config.heading_split_level)
+            .map(|(level, text, anchor)| HeadingEntry {
+                level,
+                text,
+                anchor: format!("#{}", anchor),
+            })
+            .collect()
     }
 }
 
@@ -307,18 +287,21 @@ impl<'a> SearchDocumentBuilder<'a> {
 /// - Images
 /// - HTML tags
 /// - Formatting markers
-pub fn strip_markdown(markdown: &str) -> String {
+/// - Callout markers (`> [!NOTE]`), keeping custom titles
+/// - Math (`$...$` and `$$...$$`), when `math` is enabled
+pub fn strip_markdown(markdown: &str, math: bool) -> String {
     let options = Options::ENABLE_TABLES
         | Options::ENABLE_FOOTNOTES
         | Options::ENABLE_STRIKETHROUGH
         | Options::ENABLE_TASKLISTS;
 
-    let parser = Parser::new_ext(markdown, options);
+    let math = MathText::new(markdown, math);
+    let events = strip_markers(Parser::new_ext(&math.markdown, options).collect());
 
     let mut output = String::new();
     let mut skip_depth = 0;
 
-    for event in parser {
+    for event in events {
         match event {
             // Skip code blocks entirely
             Event::Start(Tag::CodeBlock(_)) => {
@@ -337,27 +320,19 @@ pub fn strip_markdown(markdown: &str) -> String {
                 skip_depth -= 1;
             }
             // Keep text content
-            Event::Text(text) => {
-                if skip_depth == 0 {
-                    output.push_str(&text);
-                    output.push(' ');
-                }
+            Event::Text(text) if skip_depth == 0 => {
+                output.push_str(&math.strip(&text));
+                output.push(' ');
             }
             // Add newlines for structure
-            Event::SoftBreak | Event::HardBreak => {
-                if skip_depth == 0 {
-                    output.push(' ');
-                }
+            Event::SoftBreak | Event::HardBreak if skip_depth == 0 => {
+                output.push(' ');
             }
-            Event::End(TagEnd::Paragraph) => {
-                if skip_depth == 0 {
-                    output.push('\n');
-                }
+            Event::End(TagEnd::Paragraph) if skip_depth == 0 => {
+                output.push('\n');
             }
-            Event::End(TagEnd::Heading(_)) => {
-                if skip_depth == 0 {
-                    output.push('\n');
-                }
+            Event::End(TagEnd::Heading(_)) if skip_depth == 0 => {
+                output.push('\n');
             }
             _ => {}
         }
@@ -367,29 +342,6 @@ pub fn strip_markdown(markdown: &str) -> String {
     output.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-/// Convert text to a URL-safe slug.
-///
-/// Uses GitHub-compatible slugification.
-fn slugify(text: &str) -> String {
-    text.to_lowercase()
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() {
-                c
-            } else if c.is_whitespace() || c == '-' || c == '_' {
-                '-'
-            } else {
-                '\0'
-            }
-        })
-        .filter(|&c| c != '\0')
-        .collect::<String>()
-        .split('-')
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>()
-        .join("-")
-}
-
 /// Truncate text to a maximum length, breaking at word boundaries.
 fn truncate_text(text: &str, max_len: usize) -> String {
     if text.len() <= max_len {
@@ -409,6 +361,7 @@ fn truncate_text(text: &str, max_len: usize) -> String {
 mod tests {
     use super::*;
     use crate::frontmatter::AuthnLevel;
+    use crate::render::slugify;
 
     #[test]
     fn test_search_index_new() {
@@ -425,7 +378,7 @@ mod tests {
     #[test]
     fn test_strip_markdown_basic() {
         let md = "# Hello World\n\nThis is **bold** and *italic* text.";
-        let plain = strip_markdown(md);
+        let plain = strip_markdown(md, false);
         assert!(plain.contains("Hello World"));
         assert!(plain.contains("bold"));
         assert!(plain.contains("italic"));
@@ -436,7 +389,7 @@ mod tests {
     #[test]
     fn test_strip_markdown_code() {
         let md = "Some text\n\n```rust\nfn main() {}\n```\n\nMore text";
-        let plain = strip_markdown(md);
+        let plain = strip_markdown(md, false);
         assert!(plain.contains("Some text"));
         assert!(plain.contains("More text"));
         assert!(!plain.contains("fn main"));
@@ -445,7 +398,7 @@ mod tests {
     #[test]
     fn test_strip_markdown_links() {
         let md = "Check out [this link](https://example.com) for more.";
-        let plain = strip_markdown(md);
+        let plain = strip_markdown(md, false);
         assert!(plain.contains("this link"));
         assert!(!plain.contains("https://"));
     }
@@ -453,13 +406,26 @@ mod tests {
     #[test]
     fn test_strip_markdown_inline_code() {
         let md = "Use the `foo()` function.";
-        let plain = strip_markdown(md);
+        let plain = strip_markdown(md, false);
         assert!(plain.contains("Use the"));
         assert!(plain.contains("function"));
         // Inline code is stripped
         assert!(!plain.contains("`"));
     }
 
+    #[test]
+    fn test_strip_markdown_callouts() {
+        let md = "> [!WARNING]\n> Back up first.\n\n> [!TIP] Shortcut\n> Press `?`.";
+        assert_eq!(strip_markdown(md, false), "Back up first. Shortcut Press .");
+    }
+
+    #[test]
+    fn test_strip_markdown_math() {
+        let md = "Energy is $E = mc^2$.\n\n$$\n\\int_0^1 x\\,dx\n$$\n\nIt costs $5 or $10.";
+        assert_eq!(strip_markdown(md, true), "Energy is . It costs $5 or $10.");
+        assert!(strip_markdown(md, false).starts_with("Energy is $E = mc^2$."));
+    }
+
     #[test]
     fn test_extract_headings() {
         let config = SearchConfig {
@@ -531,6 +497,26 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_document_builder_hidden() {
+        let config = SearchConfig::default();
+        let builder = SearchDocumentBuilder::new(&config);
+
+        let frontmatter = Frontmatter {
+            hidden: true,
+            ..Default::default()
+        };
+
+        let result = builder.build(
+            "/hidden".to_string(),
+            "Hidden".to_string(),
+            "# Content",
+            &frontmatter,
+        );
+
+        assert!(result.is_none());
+    }
+
     #[test]
     fn test_document_builder_with_auth() {
         let config = SearchConfig {
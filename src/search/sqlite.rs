@@ -0,0 +1,220 @@
+//! SQLite FTS5 search database.
+//!
+//! Writes search.db so servers can run ranked, filtered full-text queries
+//! without loading the JSON index into memory. Requires the `sqlite-search`
+//! feature.
+//!
+//! The `search` FTS5 table holds one row per page (`kind = 'page'`) and one
+//! per indexed heading (`kind = 'heading'`). `scope`, `authn` and `authz`
+//! (a JSON array of roles) are stored unindexed for filtering:
+//!
+//! ```sql
+//! SELECT path, anchor, title, snippet(search, 4, '<mark>', '</mark>', '…', 16)
+//! FROM search
+//! WHERE search MATCH ?1 AND (scope IS NULL OR scope = ?2)
+//! ORDER BY bm25(search, 0.0, 0.0, 0.0, 10.0, 1.0)
+//! LIMIT 20;
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use super::SearchIndex;
+
+/// SQL schema for the search database.
+const SCHEMA: &str = "
+CREATE TABLE meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE VIRTUAL TABLE search USING fts5(
+    kind UNINDEXED,
+    path UNINDEXED,
+    anchor UNINDEXED,
+    title,
+    body,
+    scope UNINDEXED,
+    authn UNINDEXED,
+    authz UNINDEXED,
+    tokenize = 'porter unicode61'
+);
+";
+
+impl SearchIndex {
+    /// Write the index to a SQLite database with an FTS5 table.
+    ///
+    /// The database is built in a temporary file next to `path` and moved
+    /// over `path` only when the bytes differ, so an unchanged index leaves
+    /// the existing database untouched. Returns whether `path` was written.
+    pub fn write_sqlite(&self, path: &Path) -> Result<bool> {
+        let tmp = path.with_extension("db.tmp");
+        if tmp.exists() {
+            fs::remove_file(&tmp).with_context(|| format!("Failed to remove {}", tmp.display()))?;
+        }
+        write_database(self, &tmp)
+            .with_context(|| format!("Failed to write search database: {}", tmp.display()))?;
+
+        let built = fs::read(&tmp).with_context(|| format!("Failed to read {}", tmp.display()))?;
+        if fs::read(path).is_ok_and(|existing| existing == built) {
+            fs::remove_file(&tmp).with_context(|| format!("Failed to remove {}", tmp.display()))?;
+            return Ok(false);
+        }
+
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(true)
+    }
+}
+
+fn write_database(index: &SearchIndex, path: &Path) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut meta = tx.prepare("INSERT INTO meta (key, value) VALUES (?1, ?2)")?;
+        meta.execute(params!["schema", index.schema])?;
+        meta.execute(params!["version", index.version])?;
+        meta.execute(params![
+            "heading_split_level",
+            index.config.heading_split_level.to_string()
+        ])?;
+
+        let mut insert = tx.prepare(
+            "INSERT INTO search (kind, path, anchor, title, body, scope, authn, authz)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+
+        for doc in &index.documents {
+            let authn = doc.auth.as_ref().and_then(|a| a.authn.clone());
+            let authz = doc
+                .auth
+                .as_ref()
+                .and_then(|a| a.authz.as_ref())
+                .map(|roles| serde_json::to_string(roles).unwrap_or_default());
+
+            insert.execute(params![
+                "page",
+                doc.path,
+                None::<String>,
+                doc.title,
+                doc.body.as_deref().unwrap_or_default(),
+                doc.scope,
+                authn,
+                authz,
+            ])?;
+
+            for heading in &doc.headings {
+                insert.execute(params![
+                    "heading",
+                    doc.path,
+                    heading.anchor,
+                    heading.text,
+                    "",
+                    doc.scope,
+                    authn,
+                    authz,
+                ])?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    conn.execute_batch("INSERT INTO search (search) VALUES ('optimize');")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{DocumentAuth, HeadingEntry, SearchDocument};
+
+    fn sample_index() -> SearchIndex {
+        let mut index = SearchIndex::default();
+        index.add_document(SearchDocument {
+            path: "/install".to_string(),
+            title: "Installation".to_string(),
+            body: Some("Install the backend with cargo".to_string()),
+            headings: vec![HeadingEntry {
+                level: 2,
+                text: "Configuring cargo".to_string(),
+                anchor: "#configuring-cargo".to_string(),
+            }],
+            auth: None,
+            scope: None,
+        });
+        index.add_document(SearchDocument {
+            path: "/internal/deploy".to_string(),
+            title: "Deploying".to_string(),
+            body: Some("Deploy with cargo and kubectl".to_string()),
+            headings: vec![],
+            auth: Some(DocumentAuth {
+                authn: Some("authenticated".to_string()),
+                authz: Some(vec!["staff".to_string()]),
+            }),
+            scope: Some("internal".to_string()),
+        });
+        index
+    }
+
+    #[test]
+    fn test_write_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("search.db");
+        sample_index().write_sqlite(&path).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT count(*) FROM search", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(rows, 3); // two pages + one heading
+
+        let public: Vec<String> = conn
+            .prepare(
+                "SELECT path FROM search
+                 WHERE search MATCH 'cargo' AND kind = 'page' AND scope IS NULL",
+            )
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(public, vec!["/install"]);
+
+        let staff: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM search
+                 WHERE search MATCH 'kubectl'
+                 AND EXISTS (SELECT 1 FROM json_each(search.authz) WHERE value = 'staff')",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(staff, 1);
+    }
+
+    #[test]
+    fn test_write_database_replaces_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("search.db");
+        assert!(sample_index().write_sqlite(&path).unwrap());
+        assert!(!sample_index().write_sqlite(&path).unwrap());
+        assert!(!dir.path().join("search.db.tmp").exists());
+
+        let mut index = sample_index();
+        index.documents.pop();
+        assert!(index.write_sqlite(&path).unwrap());
+        assert!(sample_index().write_sqlite(&path).unwrap());
+
+        let conn = Connection::open(&path).unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT count(*) FROM search WHERE kind = 'page'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+}
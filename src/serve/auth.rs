@@ -0,0 +1,416 @@
+//! Access control for the development server.
+//!
+//! Verifies bearer or cookie JWTs and applies the manifest's `authn`/`authz`
+//! requirements together with `[output.htmx.authz]`.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+
+use crate::config::{AuthzConfig, DefaultAccess};
+use crate::manifest::PageEntry;
+
+/// Keys used to verify JWT signatures.
+pub enum JwtKeys {
+    /// Shared HMAC secret (HS256, HS384, HS512)
+    Secret(Vec<u8>),
+    /// Public keys from a JWKS file, selected by `kid`
+    Jwks(JwkSet),
+}
+
+impl JwtKeys {
+    /// Load a JWKS file.
+    pub fn from_jwks_file(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read JWKS: {}", path.display()))?;
+        let jwks: JwkSet = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse JWKS: {}", path.display()))?;
+        Ok(Self::Jwks(jwks))
+    }
+
+    /// Verify a token and return its claims.
+    ///
+    /// The token's `alg` must be one the key allows: HMAC for a secret, and
+    /// for a JWKS key its `alg` parameter or, without one, an algorithm of
+    /// the key's type. `exp` is required; `aud` is not checked.
+    pub fn verify(&self, token: &str) -> Result<Value> {
+        let header = jsonwebtoken::decode_header(token).context("Malformed JWT header")?;
+
+        let (key, algorithms) = match self {
+            Self::Secret(secret) => (DecodingKey::from_secret(secret), HMAC_ALGORITHMS.to_vec()),
+            Self::Jwks(jwks) => {
+                let jwk = match &header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None if jwks.keys.len() == 1 => jwks.keys.first(),
+                    None => None,
+                }
+                .context("No matching key in JWKS")?;
+                (DecodingKey::from_jwk(jwk)?, jwk_algorithms(jwk)?)
+            }
+        };
+
+        if !algorithms.contains(&header.alg) {
+            anyhow::bail!(
+                "JWT algorithm {:?} does not match the key (expected one of {:?})",
+                header.alg,
+                algorithms
+            );
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = algorithms;
+        validation.validate_aud = false;
+        let data = jsonwebtoken::decode::<Value>(token, &key, &validation)?;
+        Ok(data.claims)
+    }
+}
+
+/// HMAC algorithms accepted for shared secrets.
+const HMAC_ALGORITHMS: [Algorithm; 3] = [Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+
+/// Signature algorithms a JWKS key may verify.
+fn jwk_algorithms(jwk: &Jwk) -> Result<Vec<Algorithm>> {
+    if let Some(alg) = jwk.common.key_algorithm {
+        let algorithm = Algorithm::from_str(&alg.to_string())
+            .with_context(|| format!("JWKS key algorithm {} cannot verify signatures", alg))?;
+        return Ok(vec![algorithm]);
+    }
+
+    Ok(match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => vec![Algorithm::ES256],
+            EllipticCurve::P384 => vec![Algorithm::ES384],
+            _ => anyhow::bail!("Unsupported JWKS curve {:?}", params.curve),
+        },
+        AlgorithmParameters::OctetKeyPair(_) => vec![Algorithm::EdDSA],
+        AlgorithmParameters::OctetKey(_) => HMAC_ALGORITHMS.to_vec(),
+    })
+}
+
+/// An authenticated user, taken from verified JWT claims.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct User {
+    /// Roles from the role claim (`None` when the claim is missing)
+    pub roles: Option<Vec<String>>,
+    /// `email_verified` claim is `true`
+    pub verified: bool,
+}
+
+impl User {
+    /// Build a user from claims.
+    ///
+    /// `role_claim` may be a dotted path (e.g. `realm_access.roles`). The
+    /// claim may hold an array of strings or a space-separated string.
+    pub fn from_claims(claims: &Value, role_claim: &str) -> Self {
+        let roles = role_claim
+            .split('.')
+            .try_fold(claims, |value, key| value.get(key))
+            .and_then(|value| match value {
+                Value::Array(items) => Some(
+                    items
+                        .iter()
+                        .filter_map(|v| v.as_str().map(str::to_string))
+                        .collect(),
+                ),
+                Value::String(s) => Some(s.split_whitespace().map(str::to_string).collect()),
+                _ => None,
+            });
+
+        Self {
+            roles,
+            verified: claims.get("email_verified") == Some(&Value::Bool(true)),
+        }
+    }
+}
+
+/// Outcome of an access check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Serve the page
+    Allowed,
+    /// No valid token; respond 401
+    Unauthenticated,
+    /// Token present but insufficient; redirect to fallback or respond 403
+    Forbidden,
+}
+
+/// Decide whether `user` may see the page.
+///
+/// Pages without `authn` or `authz` follow `default-access`; with
+/// `denied` they are forbidden to everyone. `authz` grants access to users
+/// holding any of the listed roles. In `strict` mode a token without the
+/// role claim is forbidden from every non-public page.
+pub fn check_access(entry: &PageEntry, user: Option<&User>, authz: &AuthzConfig) -> Access {
+    let has_roles = entry.authz.as_ref().is_some_and(|r| !r.is_empty());
+
+    let required = match entry.authn.as_deref() {
+        Some("public") if !has_roles => return Access::Allowed,
+        Some(level) if level != "public" => level,
+        _ if has_roles => "authenticated",
+        _ => match authz.default_access {
+            DefaultAccess::Public => return Access::Allowed,
+            DefaultAccess::Authenticated => "authenticated",
+            DefaultAccess::Denied => return Access::Forbidden,
+        },
+    };
+
+    let Some(user) = user else {
+        return Access::Unauthenticated;
+    };
+
+    if required == "verified" && !user.verified {
+        return Access::Forbidden;
+    }
+
+    let roles = match (&user.roles, authz.strict) {
+        (Some(roles), _) => roles.as_slice(),
+        (None, true) => return Access::Forbidden,
+        (None, false) => &[],
+    };
+
+    match &entry.authz {
+        Some(required) if !required.is_empty() => {
+            if required.iter().any(|r| roles.contains(r)) {
+                Access::Allowed
+            } else {
+                Access::Forbidden
+            }
+        }
+        _ => Access::Allowed,
+    }
+}
+
+/// Extract a token from `Authorization: Bearer` or the session cookie.
+pub fn extract_token<'a>(
+    authorization: Option<&'a str>,
+    cookie: Option<&'a str>,
+    cookie_name: &str,
+) -> Option<&'a str> {
+    authorization
+        .and_then(|h| h.strip_prefix("Bearer ").map(str::trim))
+        .or_else(|| {
+            cookie?.split(';').find_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                (name == cookie_name).then_some(value)
+            })
+        })
+        .filter(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn entry(authn: Option<&str>, authz: Option<&[&str]>) -> PageEntry {
+        PageEntry {
+            title: "Page".to_string(),
+            source: PathBuf::from("page.md"),
+            page_path: PathBuf::from("pages/page.html"),
+            fragment_path: PathBuf::from("fragments/page.html"),
+            extended_path: None,
+            scope: None,
+            authn: authn.map(str::to_string),
+            authz: authz.map(|r| r.iter().map(|s| s.to_string()).collect()),
+            fallback: None,
+            content_hash: String::new(),
+            hidden: false,
+        }
+    }
+
+    fn user(roles: Option<&[&str]>, verified: bool) -> User {
+        User {
+            roles: roles.map(|r| r.iter().map(|s| s.to_string()).collect()),
+            verified,
+        }
+    }
+
+    #[test]
+    fn test_check_access() {
+        let config = AuthzConfig::default();
+        let staff = user(Some(&["staff"]), false);
+
+        assert_eq!(
+            check_access(&entry(None, None), None, &config),
+            Access::Allowed
+        );
+        assert_eq!(
+            check_access(&entry(Some("authenticated"), None), None, &config),
+            Access::Unauthenticated
+        );
+        assert_eq!(
+            check_access(&entry(Some("authenticated"), None), Some(&staff), &config),
+            Access::Allowed
+        );
+        assert_eq!(
+            check_access(&entry(Some("verified"), None), Some(&staff), &config),
+            Access::Forbidden
+        );
+        assert_eq!(
+            check_access(
+                &entry(None, Some(&["admin", "staff"])),
+                Some(&staff),
+                &config
+            ),
+            Access::Allowed
+        );
+        assert_eq!(
+            check_access(&entry(None, Some(&["admin"])), Some(&staff), &config),
+            Access::Forbidden
+        );
+        assert_eq!(
+            check_access(&entry(None, Some(&["admin"])), None, &config),
+            Access::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn test_check_access_defaults_and_strict() {
+        let no_claim = user(None, true);
+        let page = entry(None, None);
+
+        let config = AuthzConfig {
+            default_access: DefaultAccess::Authenticated,
+            ..Default::default()
+        };
+        assert_eq!(check_access(&page, None, &config), Access::Unauthenticated);
+        assert_eq!(
+            check_access(&page, Some(&no_claim), &config),
+            Access::Allowed
+        );
+
+        let config = AuthzConfig {
+            default_access: DefaultAccess::Denied,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_access(&page, Some(&no_claim), &config),
+            Access::Forbidden
+        );
+        assert_eq!(
+            check_access(&entry(Some("public"), None), None, &config),
+            Access::Allowed
+        );
+
+        let config = AuthzConfig {
+            strict: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_access(
+                &entry(Some("authenticated"), None),
+                Some(&no_claim),
+                &config
+            ),
+            Access::Forbidden
+        );
+        assert_eq!(
+            check_access(&page, Some(&no_claim), &config),
+            Access::Allowed
+        );
+    }
+
+    #[test]
+    fn test_user_from_claims() {
+        let claims = json!({
+            "roles": ["staff", "admin"],
+            "realm_access": { "roles": "editor viewer" },
+            "email_verified": true
+        });
+
+        let u = User::from_claims(&claims, "roles");
+        assert_eq!(
+            u.roles,
+            Some(vec!["staff".to_string(), "admin".to_string()])
+        );
+        assert!(u.verified);
+
+        let u = User::from_claims(&claims, "realm_access.roles");
+        assert_eq!(
+            u.roles,
+            Some(vec!["editor".to_string(), "viewer".to_string()])
+        );
+
+        assert_eq!(User::from_claims(&claims, "groups").roles, None);
+    }
+
+    #[test]
+    fn test_extract_token() {
+        assert_eq!(
+            extract_token(Some("Bearer abc"), None, "session"),
+            Some("abc")
+        );
+        assert_eq!(
+            extract_token(None, Some("theme=dark; session=xyz"), "session"),
+            Some("xyz")
+        );
+        assert_eq!(extract_token(Some("Basic abc"), None, "session"), None);
+        assert_eq!(extract_token(None, Some("other=1"), "session"), None);
+    }
+
+    #[test]
+    fn test_verify_hmac() {
+        let keys = JwtKeys::Secret(b"secret".to_vec());
+        let claims = json!({ "sub": "u1", "roles": ["staff"], "exp": 4_000_000_000u64 });
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        assert_eq!(keys.verify(&token).unwrap()["sub"], "u1");
+        assert!(JwtKeys::Secret(b"other".to_vec()).verify(&token).is_err());
+
+        let expired = jsonwebtoken::encode(
+            &Header::default(),
+            &json!({ "sub": "u1", "exp": 1 }),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(keys.verify(&expired).is_err());
+    }
+
+    #[test]
+    fn test_verify_jwks_algorithm() {
+        // Symmetric JWKS key for the secret "secret", pinned to HS256
+        let jwks = |alg: &str| {
+            let json = format!(
+                r#"{{"keys": [{{"kty": "oct", "kid": "k1", {}"k": "c2VjcmV0"}}]}}"#,
+                alg
+            );
+            JwtKeys::Jwks(serde_json::from_str(&json).unwrap())
+        };
+        let claims = json!({ "sub": "u1", "exp": 4_000_000_000u64 });
+        let token = |alg| {
+            let mut header = Header::new(alg);
+            header.kid = Some("k1".to_string());
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap()
+        };
+
+        let pinned = jwks(r#""alg": "HS256", "#);
+        assert_eq!(
+            pinned.verify(&token(Algorithm::HS256)).unwrap()["sub"],
+            "u1"
+        );
+        // The header's alg must match the key's, even when the signature does
+        assert!(pinned.verify(&token(Algorithm::HS384)).is_err());
+
+        // Without an alg parameter, the key type decides
+        assert!(jwks("").verify(&token(Algorithm::HS384)).is_ok());
+    }
+}
@@ -0,0 +1,902 @@
+//! Development server.
+//!
+//! A reference implementation of the unified server model (ADR-0001) for
+//! trying a build locally. Pages are looked up in manifest.json and served
+//! from `pages/`, or from `fragments/` when the request carries
+//! `HX-Request: true`. Files under `assets/` and `assets-manifest.json` are
+//! served as static files. `book.json` and the search indexes are served
+//! to users who may open every page they contain; other build output files
+//! are not served.
+//!
+//! Manifest `authn`/`authz` requirements are enforced with JWTs from the
+//! `Authorization: Bearer` header or the session cookie (see [`auth`]).
+//!
+//! Every response is `Cache-Control: no-cache` so rebuilds show up on the
+//! next request. It is not meant for production.
+
+pub mod auth;
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use tera::Tera;
+
+use crate::config::HtmxConfig;
+use crate::manifest::{extended_url, Manifest, PageEntry};
+use crate::render::oob::url_with_base;
+use crate::templates;
+
+use self::auth::{check_access, extract_token, Access, JwtKeys, User};
+
+/// Options for [`DevServer`].
+pub struct ServeOptions {
+    /// Build output directory (containing manifest.json)
+    pub root: PathBuf,
+    /// `[output.htmx]` config (base path, auth settings)
+    pub config: HtmxConfig,
+    /// `[book]` table, available to the error templates as `config.book`
+    pub book: serde_json::Value,
+    /// Theme directory with template overrides
+    pub theme_dir: Option<PathBuf>,
+    /// Keys for verifying JWTs (`None` treats every request as anonymous)
+    pub keys: Option<JwtKeys>,
+}
+
+impl ServeOptions {
+    /// Options for `root` using the config in `book_dir/book.toml`.
+    ///
+    /// Defaults apply when there is no book.toml.
+    pub fn from_book_dir(root: PathBuf, book_dir: &Path) -> Result<Self> {
+        let path = book_dir.join("book.toml");
+        let book_toml: toml::Value = if path.is_file() {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            toml::Value::Table(Default::default())
+        };
+
+        let config = HtmxConfig::from_toml(book_toml.get("output").and_then(|o| o.get("htmx")))?;
+        config.validate()?;
+        let book = book_toml
+            .get("book")
+            .map(serde_json::to_value)
+            .transpose()?
+            .unwrap_or_default();
+        let theme_dir = config.theme_dir.as_ref().map(|dir| book_dir.join(dir));
+
+        Ok(Self {
+            root,
+            config,
+            book,
+            theme_dir,
+            keys: None,
+        })
+    }
+}
+
+/// A request, reduced to what routing needs.
+#[derive(Debug, Clone, Default)]
+pub struct ServeRequest {
+    /// Request path (query string is ignored)
+    pub path: String,
+    /// `HX-Request: true` was sent
+    pub hx_request: bool,
+    /// `HX-History-Restore-Request: true` was sent
+    pub history_restore: bool,
+    /// `Authorization` header
+    pub authorization: Option<String>,
+    /// `Cookie` header
+    pub cookie: Option<String>,
+}
+
+/// A response ready to be written to the client.
+#[derive(Debug, Clone)]
+pub struct ServeResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// Response headers
+    pub headers: Vec<(String, String)>,
+    /// Response body
+    pub body: Vec<u8>,
+}
+
+/// Which representation of a page to serve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKind {
+    /// Full HTML page from `pages/`
+    Page,
+    /// Content fragment from `fragments/`
+    Fragment,
+    /// Lazily loaded extended fragment (`PageEntry.extended_path`)
+    Extended,
+}
+
+/// Result of routing a request path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    /// Health check
+    Health,
+    /// A manifest page
+    Page {
+        /// Manifest key (URL path)
+        url: String,
+        /// Representation to serve
+        kind: PageKind,
+    },
+    /// A public file in the build output, relative to the root
+    Static(PathBuf),
+    /// A build output file holding page content, served under the access
+    /// rules of those pages
+    Restricted(PathBuf),
+    /// Nothing matched
+    NotFound,
+}
+
+/// Serves a build output directory.
+pub struct DevServer {
+    root: PathBuf,
+    base_path: String,
+    config: HtmxConfig,
+    book: serde_json::Value,
+    keys: Option<JwtKeys>,
+    tera: Tera,
+}
+
+impl DevServer {
+    /// Create a server for the given build output.
+    ///
+    /// Fails if the directory has no manifest.json.
+    pub fn new(options: ServeOptions) -> Result<Self> {
+        let manifest = options.root.join("manifest.json");
+        if !manifest.is_file() {
+            anyhow::bail!(
+                "No manifest.json in {}; run `mdbook build` first",
+                options.root.display()
+            );
+        }
+
+        let tera = templates::init_templates(options.theme_dir.as_deref())?;
+        let base_path = options.config.base_url();
+
+        Ok(Self {
+            root: options.root,
+            base_path,
+            config: options.config,
+            book: options.book,
+            keys: options.keys,
+            tera,
+        })
+    }
+
+    /// Listen on `addr` and serve requests until the process exits.
+    pub fn run(&self, addr: &str) -> Result<()> {
+        let server = tiny_http::Server::http(addr)
+            .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", addr, e))?;
+        info!(
+            "Serving {} at http://{}{}",
+            self.root.display(),
+            addr,
+            self.base_path
+        );
+
+        for request in server.incoming_requests() {
+            let serve_request = ServeRequest {
+                path: request.url().to_string(),
+                hx_request: header_is_true(&request, "HX-Request"),
+                history_restore: header_is_true(&request, "HX-History-Restore-Request"),
+                authorization: header_value(&request, "Authorization"),
+                cookie: header_value(&request, "Cookie"),
+            };
+
+            let response = self.respond(&serve_request);
+            debug!(
+                "{} {} (hx={}) -> {}",
+                request.method(),
+                serve_request.path,
+                serve_request.hx_request,
+                response.status
+            );
+
+            let mut reply =
+                tiny_http::Response::from_data(response.body).with_status_code(response.status);
+            for (name, value) in &response.headers {
+                if let Ok(header) = tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes())
+                {
+                    reply.add_header(header);
+                }
+            }
+            if let Err(e) = request.respond(reply) {
+                warn!("Failed to send response: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the response for a request.
+    pub fn respond(&self, request: &ServeRequest) -> ServeResponse {
+        let manifest = match self.load_manifest() {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!("{:#}", e);
+                return text_response(500, "Failed to load manifest.json");
+            }
+        };
+
+        // History restores need the whole page, not a fragment
+        let hx_request = request.hx_request && !request.history_restore;
+
+        match self.route(&manifest, &request.path, hx_request) {
+            Route::Health => text_response(200, "healthy\n"),
+            Route::Page { url, kind } => {
+                let entry = &manifest.pages[&url];
+                let user = self.authenticate(request);
+                match check_access(entry, user.as_ref(), &self.config.authz) {
+                    Access::Allowed => self.page_response(entry, kind),
+                    Access::Unauthenticated => self.error_page(401),
+                    Access::Forbidden => self.forbidden(&manifest, &url, entry),
+                }
+            }
+            Route::Static(path) => self.static_response(&path),
+            Route::Restricted(path) => {
+                let user = self.authenticate(request);
+                let access = covered_pages(&manifest, &path)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|entry| check_access(entry, user.as_ref(), &self.config.authz))
+                    .find(|access| *access != Access::Allowed)
+                    .unwrap_or(Access::Allowed);
+                match access {
+                    Access::Allowed => self.static_response(&path),
+                    Access::Unauthenticated => self.error_page(401),
+                    Access::Forbidden => self.error_page(403),
+                }
+            }
+            Route::NotFound => text_response(404, "Not Found"),
+        }
+    }
+
+    /// Map a request path to a route.
+    ///
+    /// Manifest pages match by URL (`/guide/intro`, `/guide/intro.html`,
+    /// `/guide/`) or by their page or fragment file path. `hx_request`
+    /// selects the fragment for URL matches. Extended fragments match by
+    /// their URL (`/guide/intro/extended`) or file path. Other files are
+    /// only routed when they are public or hold page content.
+    pub fn route(&self, manifest: &Manifest, path: &str, hx_request: bool) -> Route {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let Some(relative) = path
+            .strip_prefix(self.base_path.as_str())
+            .or_else(|| (path.to_string() + "/" == self.base_path).then_some(""))
+        else {
+            return Route::NotFound;
+        };
+
+        if relative == "health" {
+            return Route::Health;
+        }
+
+        let kind = if hx_request {
+            PageKind::Fragment
+        } else {
+            PageKind::Page
+        };
+        let url = format!("/{}", relative);
+        let candidates = [
+            url.clone(),
+            url.trim_end_matches(".html").to_string(),
+            format!("{}/", url.trim_end_matches('/')),
+        ];
+        if let Some(key) = candidates.iter().find(|c| manifest.pages.contains_key(*c)) {
+            return Route::Page {
+                url: key.clone(),
+                kind,
+            };
+        }
+
+        let Some(file) = safe_relative_path(relative) else {
+            return Route::NotFound;
+        };
+
+        // Direct requests for generated files still map back to their page
+        for (key, entry) in &manifest.pages {
+            let Some(ref extended) = entry.extended_path else {
+                continue;
+            };
+            if extended_url(key) == url || *extended == file {
+                return Route::Page {
+                    url: key.clone(),
+                    kind: PageKind::Extended,
+                };
+            }
+        }
+        for (url, entry) in &manifest.pages {
+            if entry.page_path == file {
+                return Route::Page {
+                    url: url.clone(),
+                    kind: PageKind::Page,
+                };
+            }
+            if entry.fragment_path == file {
+                return Route::Page {
+                    url: url.clone(),
+                    kind: PageKind::Fragment,
+                };
+            }
+        }
+
+        if !self.root.join(&file).is_file() {
+            Route::NotFound
+        } else if file.starts_with("assets") || file == Path::new("assets-manifest.json") {
+            Route::Static(file)
+        } else if covered_pages(manifest, &file).is_some() {
+            Route::Restricted(file)
+        } else {
+            Route::NotFound
+        }
+    }
+
+    /// Verify the request's token, if any.
+    ///
+    /// Invalid tokens are treated as anonymous.
+    fn authenticate(&self, request: &ServeRequest) -> Option<User> {
+        let token = extract_token(
+            request.authorization.as_deref(),
+            request.cookie.as_deref(),
+            &self.config.authn.session_cookie,
+        )?;
+        let Some(keys) = &self.keys else {
+            debug!("Ignoring token: no JWT secret or JWKS configured");
+            return None;
+        };
+
+        match keys.verify(token) {
+            Ok(claims) => Some(User::from_claims(&claims, &self.config.authz.role_claim)),
+            Err(e) => {
+                debug!("Rejected token: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Redirect to the page's fallback, or the configured default fallback,
+    /// when it exists; otherwise respond 403.
+    fn forbidden(&self, manifest: &Manifest, url: &str, entry: &PageEntry) -> ServeResponse {
+        let fallback = entry
+            .fallback
+            .as_deref()
+            .unwrap_or(&self.config.authz.default_fallback);
+
+        if fallback != url && manifest.pages.contains_key(fallback) {
+            let location = url_with_base(&self.base_path, fallback);
+            return ServeResponse {
+                status: 302,
+                headers: vec![
+                    header("Location", &location),
+                    header("Vary", "HX-Request"),
+                    header("Cache-Control", "no-cache"),
+                ],
+                body: Vec::new(),
+            };
+        }
+
+        self.error_page(403)
+    }
+
+    /// Render `401.html` or `403.html`.
+    fn error_page(&self, status: u16) -> ServeResponse {
+        let mut context = tera::Context::new();
+        context.insert(
+            "config",
+            &serde_json::json!({ "book": self.book, "base_url": self.base_path }),
+        );
+        context.insert("signin_url", &self.config.authn.signin);
+        context.insert("signout_url", &self.config.authn.signout);
+
+        match self.tera.render(&format!("{}.html", status), &context) {
+            Ok(html) => ServeResponse {
+                status,
+                headers: vec![
+                    header("Content-Type", "text/html; charset=utf-8"),
+                    header("Vary", "HX-Request"),
+                    header("Cache-Control", "no-cache"),
+                ],
+                body: html.into_bytes(),
+            },
+            Err(e) => {
+                warn!("Failed to render {}.html: {:#}", status, e);
+                text_response(
+                    status,
+                    if status == 401 {
+                        "Unauthorized"
+                    } else {
+                        "Forbidden"
+                    },
+                )
+            }
+        }
+    }
+
+    /// Read manifest.json from the build output.
+    ///
+    /// Read on every request so rebuilds are picked up without a restart.
+    fn load_manifest(&self) -> Result<Manifest> {
+        let path = self.root.join("manifest.json");
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn page_response(&self, entry: &PageEntry, kind: PageKind) -> ServeResponse {
+        let fragment = self.root.join(&entry.fragment_path);
+        // Full-only builds have no fragments
+        let file = match (kind, &entry.extended_path) {
+            (PageKind::Extended, Some(extended)) => self.root.join(extended),
+            (PageKind::Fragment, _) if fragment.is_file() => fragment,
+            _ => self.root.join(&entry.page_path),
+        };
+
+        match fs::read(&file) {
+            Ok(body) => ServeResponse {
+                status: 200,
+                headers: vec![
+                    header("Content-Type", "text/html; charset=utf-8"),
+                    header("Vary", "HX-Request"),
+                    header("Cache-Control", "no-cache"),
+                ],
+                body,
+            },
+            Err(e) => {
+                warn!("Failed to read {}: {}", file.display(), e);
+                text_response(404, "Not Found")
+            }
+        }
+    }
+
+    fn static_response(&self, path: &Path) -> ServeResponse {
+        match fs::read(self.root.join(path)) {
+            Ok(body) => ServeResponse {
+                status: 200,
+                headers: vec![
+                    header("Content-Type", content_type(path)),
+                    header("Cache-Control", "no-cache"),
+                ],
+                body,
+            },
+            Err(_) => text_response(404, "Not Found"),
+        }
+    }
+}
+
+/// Page entries whose access rules apply to a build output file, or None
+/// for files that hold no page content the server hands out.
+///
+/// `book.json` and `search-index.json` contain every page, so serving them
+/// needs access to all of them. A search shard needs the access of its
+/// audience, expressed as a page entry with that audience's requirements.
+fn covered_pages(manifest: &Manifest, file: &Path) -> Option<Vec<PageEntry>> {
+    if file == Path::new("book.json") || file == Path::new("search-index.json") {
+        return Some(manifest.pages.values().cloned().collect());
+    }
+
+    let shards = manifest.search.as_ref()?;
+    let audience = |authn: &str, role: Option<&String>| PageEntry {
+        title: String::new(),
+        source: PathBuf::new(),
+        page_path: PathBuf::new(),
+        fragment_path: PathBuf::new(),
+        extended_path: None,
+        scope: None,
+        authn: Some(authn.to_string()),
+        authz: role.map(|r| vec![r.clone()]),
+        fallback: None,
+        content_hash: String::new(),
+        hidden: false,
+    };
+    let entry = if file == Path::new(&shards.public) {
+        audience("public", None)
+    } else if file == Path::new(&shards.authenticated) {
+        audience("authenticated", None)
+    } else if file == Path::new(&shards.verified) {
+        audience("verified", None)
+    } else if let Some((role, _)) = shards
+        .roles
+        .iter()
+        .find(|(_, shard)| file == Path::new(shard))
+    {
+        audience("authenticated", Some(role))
+    } else {
+        let (role, _) = shards
+            .verified_roles
+            .iter()
+            .find(|(_, shard)| file == Path::new(shard))?;
+        audience("verified", Some(role))
+    };
+    Some(vec![entry])
+}
+
+/// Convert a URL path to a relative file path, rejecting `..` and absolute
+/// components.
+fn safe_relative_path(relative: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(relative);
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(path)
+        .filter(|p| !p.as_os_str().is_empty())
+}
+
+/// Guess a Content-Type from the file extension.
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn header(name: &str, value: &str) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+fn text_response(status: u16, body: &str) -> ServeResponse {
+    ServeResponse {
+        status,
+        headers: vec![
+            header("Content-Type", "text/plain; charset=utf-8"),
+            header("Cache-Control", "no-cache"),
+        ],
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+fn header_is_true(request: &tiny_http::Request, name: &str) -> bool {
+    header_value(request, name).is_some_and(|v| v.eq_ignore_ascii_case("true"))
+}
+
+fn header_value(request: &tiny_http::Request, name: &str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::SearchShards;
+
+    fn fixture(base_path: &str) -> (tempfile::TempDir, DevServer) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (file, body) in [
+            ("pages/README.html", "<html>home</html>"),
+            ("pages/guide/intro.html", "<html>intro page</html>"),
+            ("fragments/guide/intro.html", "<main>intro fragment</main>"),
+            (
+                "fragments/guide/intro.extended.html",
+                "<div>intro extended</div>",
+            ),
+            ("pages/staff.html", "<html>staff</html>"),
+            ("pages/admin.html", "<html>admin</html>"),
+            ("assets/style.css", "body {}"),
+            ("book.json", "{}"),
+            ("search-index.json", "{}"),
+            ("search-index.public.json", "{}"),
+            ("search-index.authenticated.json", "{}"),
+            ("search-index.verified.json", "{}"),
+            ("search-index.role.staff.json", "{}"),
+            ("search-index.role.staff.verified.json", "{}"),
+            ("scopes/internal/pages/staff.html", "<html>staff</html>"),
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, body).unwrap();
+        }
+
+        let mut manifest = Manifest::new();
+        let pages = [
+            ("/", "README.md", None, None),
+            ("/guide/intro", "guide/intro.md", None, None),
+            ("/staff", "staff.md", Some("staff"), None),
+            ("/admin", "admin.md", Some("admin"), Some("/staff")),
+        ];
+        for (url, source, role, fallback) in pages {
+            let html = PathBuf::from(source).with_extension("html");
+            manifest.add_page(
+                url.to_string(),
+                PageEntry {
+                    title: url.to_string(),
+                    source: PathBuf::from(source),
+                    page_path: PathBuf::from("pages").join(&html),
+                    fragment_path: PathBuf::from("fragments").join(&html),
+                    extended_path: (url == "/guide/intro")
+                        .then(|| PathBuf::from("fragments/guide/intro.extended.html")),
+                    scope: None,
+                    authn: None,
+                    authz: role.map(|r: &str| vec![r.to_string()]),
+                    fallback: fallback.map(str::to_string),
+                    content_hash: String::new(),
+                    hidden: false,
+                },
+            );
+        }
+        manifest.search = Some(SearchShards {
+            public: "search-index.public.json".to_string(),
+            authenticated: "search-index.authenticated.json".to_string(),
+            verified: "search-index.verified.json".to_string(),
+            roles: [(
+                "staff".to_string(),
+                "search-index.role.staff.json".to_string(),
+            )]
+            .into(),
+            verified_roles: [(
+                "staff".to_string(),
+                "search-index.role.staff.verified.json".to_string(),
+            )]
+            .into(),
+        });
+        fs::write(root.join("manifest.json"), manifest.to_json().unwrap()).unwrap();
+
+        let server = DevServer::new(ServeOptions {
+            root: root.to_path_buf(),
+            config: HtmxConfig {
+                base_path: base_path.to_string(),
+                ..Default::default()
+            },
+            book: serde_json::json!({ "title": "Test Book" }),
+            theme_dir: None,
+            keys: Some(JwtKeys::Secret(b"secret".to_vec())),
+        })
+        .unwrap();
+        (dir, server)
+    }
+
+    fn get(server: &DevServer, path: &str, hx_request: bool) -> ServeResponse {
+        server.respond(&ServeRequest {
+            path: path.to_string(),
+            hx_request,
+            ..Default::default()
+        })
+    }
+
+    fn header_value<'a>(response: &'a ServeResponse, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_hx_request_selects_fragment() {
+        let (_dir, server) = fixture("/");
+
+        let page = get(&server, "/guide/intro", false);
+        assert_eq!(page.status, 200);
+        assert_eq!(page.body, b"<html>intro page</html>");
+        assert_eq!(header_value(&page, "Vary"), Some("HX-Request"));
+
+        let fragment = get(&server, "/guide/intro", true);
+        assert_eq!(fragment.body, b"<main>intro fragment</main>");
+        assert_eq!(header_value(&fragment, "Vary"), Some("HX-Request"));
+
+        let extended = get(&server, "/guide/intro/extended", true);
+        assert_eq!(extended.body, b"<div>intro extended</div>");
+
+        let restore = server.respond(&ServeRequest {
+            path: "/guide/intro".to_string(),
+            hx_request: true,
+            history_restore: true,
+            ..Default::default()
+        });
+        assert_eq!(restore.body, b"<html>intro page</html>");
+    }
+
+    fn token(roles: &[&str]) -> String {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &serde_json::json!({ "sub": "u1", "roles": roles, "exp": 4_000_000_000u64 }),
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_access_control() {
+        let (_dir, server) = fixture("/");
+        let request = |path: &str, authorization: Option<String>, cookie: Option<String>| {
+            server.respond(&ServeRequest {
+                path: path.to_string(),
+                authorization,
+                cookie,
+                ..Default::default()
+            })
+        };
+
+        // No token: 401 page with sign-in link
+        let anonymous = request("/staff", None, None);
+        assert_eq!(anonymous.status, 401);
+        let body = String::from_utf8(anonymous.body).unwrap();
+        assert!(body.contains("Test Book"));
+        assert!(body.contains("Authentication Required"));
+
+        // Fragments are protected too
+        assert_eq!(request("/fragments/staff.html", None, None).status, 401);
+
+        let staff = Some(format!("Bearer {}", token(&["staff"])));
+        assert_eq!(request("/staff", staff.clone(), None).status, 200);
+        assert_eq!(
+            request(
+                "/staff",
+                None,
+                Some(format!("session={}", token(&["staff"])))
+            )
+            .status,
+            200
+        );
+
+        // Wrong role with a fallback redirects
+        let redirect = request("/admin", staff, None);
+        assert_eq!(redirect.status, 302);
+        assert_eq!(header_value(&redirect, "Location"), Some("/staff"));
+
+        // Wrong role without a fallback is 403
+        let viewer = Some(format!("Bearer {}", token(&["viewer"])));
+        assert_eq!(request("/staff", viewer, None).status, 403);
+
+        // Invalid signatures are anonymous
+        let forged = Some("Bearer not.a.jwt".to_string());
+        assert_eq!(request("/staff", forged, None).status, 401);
+    }
+
+    #[test]
+    fn test_output_file_access() {
+        let (_dir, server) = fixture("/");
+        let request = |path: &str, roles: Option<&[&str]>| {
+            server.respond(&ServeRequest {
+                path: path.to_string(),
+                authorization: roles.map(|r| format!("Bearer {}", token(r))),
+                ..Default::default()
+            })
+        };
+
+        // Files covering protected pages need a token
+        assert_eq!(request("/book.json", None).status, 401);
+        assert_eq!(request("/search-index.json", None).status, 401);
+        assert_eq!(request("/search-index.role.staff.json", None).status, 401);
+        assert_eq!(request("/search-index.public.json", None).status, 200);
+
+        let staff: &[&str] = &["staff"];
+        assert_eq!(
+            request("/search-index.role.staff.json", Some(staff)).status,
+            200
+        );
+        assert_eq!(
+            request("/search-index.authenticated.json", Some(staff)).status,
+            200
+        );
+        assert_eq!(
+            request("/search-index.role.staff.json", Some(&["viewer"])).status,
+            403
+        );
+        // The verified role shard also needs a verified email
+        assert_eq!(
+            request("/search-index.role.staff.verified.json", Some(staff)).status,
+            403
+        );
+        let verified = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &serde_json::json!({
+                "sub": "u1",
+                "roles": staff,
+                "email_verified": true,
+                "exp": 4_000_000_000u64,
+            }),
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        let response = server.respond(&ServeRequest {
+            path: "/search-index.role.staff.verified.json".to_string(),
+            authorization: Some(format!("Bearer {}", verified)),
+            ..Default::default()
+        });
+        assert_eq!(response.status, 200);
+
+        // book.json also holds the admin page
+        assert_eq!(request("/book.json", Some(staff)).status, 403);
+        assert_eq!(request("/book.json", Some(&["staff", "admin"])).status, 200);
+
+        // Unlisted output files are not served at all
+        assert_eq!(request("/manifest.json", None).status, 404);
+        assert_eq!(
+            request("/scopes/internal/pages/staff.html", Some(staff)).status,
+            404
+        );
+    }
+
+    #[test]
+    fn test_route() {
+        let (_dir, server) = fixture("/");
+        let manifest = server.load_manifest().unwrap();
+
+        let page = |url: &str, kind| Route::Page {
+            url: url.to_string(),
+            kind,
+        };
+        assert_eq!(
+            server.route(&manifest, "/", false),
+            page("/", PageKind::Page)
+        );
+        assert_eq!(
+            server.route(&manifest, "/guide/intro.html?x=1", false),
+            page("/guide/intro", PageKind::Page)
+        );
+        assert_eq!(
+            server.route(&manifest, "/fragments/guide/intro.html", false),
+            page("/guide/intro", PageKind::Fragment)
+        );
+        assert_eq!(
+            server.route(&manifest, "/guide/intro/extended", true),
+            page("/guide/intro", PageKind::Extended)
+        );
+        assert_eq!(
+            server.route(&manifest, "/fragments/guide/intro.extended.html", false),
+            page("/guide/intro", PageKind::Extended)
+        );
+        assert_eq!(
+            server.route(&manifest, "/staff/extended", true),
+            Route::NotFound
+        );
+        assert_eq!(
+            server.route(&manifest, "/assets/style.css", false),
+            Route::Static(PathBuf::from("assets/style.css"))
+        );
+        assert_eq!(server.route(&manifest, "/health", false), Route::Health);
+        assert_eq!(server.route(&manifest, "/missing", false), Route::NotFound);
+        assert_eq!(
+            server.route(&manifest, "/../manifest.json", false),
+            Route::NotFound
+        );
+    }
+
+    #[test]
+    fn test_base_path() {
+        let (_dir, server) = fixture("/docs");
+
+        assert_eq!(get(&server, "/docs", false).body, b"<html>home</html>");
+        assert_eq!(get(&server, "/docs/guide/intro", true).status, 200);
+        assert_eq!(get(&server, "/guide/intro", false).status, 404);
+
+        let css = get(&server, "/docs/assets/style.css", false);
+        assert_eq!(css.status, 200);
+        assert_eq!(
+            header_value(&css, "Content-Type"),
+            Some("text/css; charset=utf-8")
+        );
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut options =
+            ServeOptions::from_book_dir(dir.path().to_path_buf(), dir.path()).unwrap();
+        options.keys = None;
+        let result = DevServer::new(options);
+        assert!(result.is_err());
+    }
+}
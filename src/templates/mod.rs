@@ -2,11 +2,22 @@
 //!
 //! Configures Tera with embedded templates and custom filters/functions.
 
-use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use log::debug;
 use tera::Tera;
+use walkdir::WalkDir;
+
+use crate::assets::AssetManifest;
+use crate::render::oob::{path_to_url, url_with_base};
+use crate::BuildError;
 
 /// Embedded templates compiled into the binary.
-const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+pub(crate) const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
     ("layout.html", include_str!("../../templates/layout.html")),
     (
         "docs/page.html",
@@ -17,6 +28,10 @@ const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
         include_str!("../../templates/docs/fragment.html"),
     ),
     ("nav.html", include_str!("../../templates/nav.html")),
+    (
+        "partials/nav-tree.html",
+        include_str!("../../templates/partials/nav-tree.html"),
+    ),
     (
         "partials/sidebar-oob.html",
         include_str!("../../templates/partials/sidebar-oob.html"),
@@ -25,6 +40,18 @@ const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
         "partials/breadcrumb.html",
         include_str!("../../templates/partials/breadcrumb.html"),
     ),
+    (
+        "partials/toc.html",
+        include_str!("../../templates/partials/toc.html"),
+    ),
+    (
+        "partials/toc-tree.html",
+        include_str!("../../templates/partials/toc-tree.html"),
+    ),
+    (
+        "partials/extended.html",
+        include_str!("../../templates/partials/extended.html"),
+    ),
     (
         "partials/loading.html",
         include_str!("../../templates/partials/loading.html"),
@@ -50,24 +77,89 @@ const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
 /// Initialize the Tera template engine with embedded templates.
 ///
 /// Templates are embedded at compile time from the templates/ directory.
+/// When a theme directory is given, every `.html` file in it is registered
+/// under its path relative to the directory, replacing any builtin template
+/// with the same name.
+///
+/// # Arguments
+/// * `theme_dir` - Optional theme directory with template overrides
 ///
 /// # Returns
 /// Configured Tera instance
-pub fn init_templates() -> Result<Tera> {
+pub fn init_templates(theme_dir: Option<&Path>) -> Result<Tera> {
     let mut tera = Tera::default();
 
-    // Add embedded templates
-    tera.add_raw_templates(BUILTIN_TEMPLATES.iter().copied())?;
+    let mut templates: Vec<(String, String)> = BUILTIN_TEMPLATES
+        .iter()
+        .map(|(name, content)| (name.to_string(), content.to_string()))
+        .collect();
+
+    // Theme templates override builtins with the same name
+    if let Some(dir) = theme_dir {
+        for (name, content) in load_theme_templates(dir)? {
+            match templates.iter_mut().find(|(n, _)| *n == name) {
+                Some(existing) => {
+                    debug!("Theme overrides template: {}", name);
+                    existing.1 = content;
+                }
+                None => {
+                    debug!("Theme adds template: {}", name);
+                    templates.push((name, content));
+                }
+            }
+        }
+    }
+
+    // Add all templates at once so inheritance resolves across both sets
+    tera.add_raw_templates(templates)?;
 
     // Register custom filters
     register_filters(&mut tera);
 
-    // Register custom functions
-    register_functions(&mut tera);
+    // Register custom functions (without assets until the build provides them)
+    register_functions(&mut tera, UrlContext::default());
 
     Ok(tera)
 }
 
+/// Load all `.html` templates from a theme directory.
+///
+/// Template names are paths relative to the theme directory using `/`
+/// separators (e.g., `partials/breadcrumb.html`).
+fn load_theme_templates(dir: &Path) -> Result<Vec<(String, String)>> {
+    if !dir.is_dir() {
+        return Err(BuildError::ConfigError(format!(
+            "Theme directory not found: {}",
+            dir.display()
+        ))
+        .into());
+    }
+
+    let mut templates = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry =
+            entry.with_context(|| format!("Failed to read theme directory: {}", dir.display()))?;
+        let path = entry.path();
+
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+
+        let name = path
+            .strip_prefix(dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme template: {}", path.display()))?;
+
+        templates.push((name, content));
+    }
+
+    Ok(templates)
+}
+
 /// Register custom Tera filters.
 fn register_filters(tera: &mut Tera) {
     // slugify: Convert string to URL-safe slug
@@ -77,9 +169,77 @@ fn register_filters(tera: &mut Tera) {
     tera.register_filter("truncate_words", truncate_words_filter);
 }
 
+/// Data needed by the URL functions available in templates.
+#[derive(Debug, Clone)]
+pub struct UrlContext {
+    /// Copied assets
+    pub assets: AssetManifest,
+    /// URL prefix the site is served under, ending in `/`
+    pub base_url: String,
+}
+
+impl Default for UrlContext {
+    fn default() -> Self {
+        Self {
+            assets: AssetManifest::new(),
+            base_url: "/".to_string(),
+        }
+    }
+}
+
+impl UrlContext {
+    /// URL of the full page for a chapter path (e.g., `guide/install.md`).
+    pub fn page_url(&self, path: &str) -> String {
+        url_with_base(&self.base_url, &path_to_url(Path::new(path)))
+    }
+}
+
 /// Register custom Tera functions.
-fn register_functions(_tera: &mut Tera) {
-    // TODO: Register functions like asset_url, fragment_url in Stage 2
+///
+/// - `asset_url(path)` - URL of a copied (possibly fingerprinted) asset
+/// - `asset_integrity(path)` - SRI hash of a copied asset (empty if disabled)
+/// - `page_url(path)` - URL of the full page for a chapter path
+///
+/// Registering again replaces the functions, so the renderer can call this
+/// once the assets have been copied.
+pub fn register_functions(tera: &mut Tera, urls: UrlContext) {
+    let urls = Arc::new(urls);
+
+    let ctx = Arc::clone(&urls);
+    tera.register_function("asset_url", move |args: &HashMap<String, tera::Value>| {
+        let path = path_arg("asset_url", args)?;
+        ctx.assets
+            .get(path)
+            .map(|entry| tera::Value::String(entry.url.clone()))
+            .ok_or_else(|| tera::Error::msg(format!("asset_url: unknown asset '{}'", path)))
+    });
+
+    let ctx = Arc::clone(&urls);
+    tera.register_function(
+        "asset_integrity",
+        move |args: &HashMap<String, tera::Value>| {
+            let path = path_arg("asset_integrity", args)?;
+            ctx.assets
+                .get(path)
+                .map(|entry| tera::Value::String(entry.integrity.clone().unwrap_or_default()))
+                .ok_or_else(|| {
+                    tera::Error::msg(format!("asset_integrity: unknown asset '{}'", path))
+                })
+        },
+    );
+
+    let ctx = urls;
+    tera.register_function("page_url", move |args: &HashMap<String, tera::Value>| {
+        let path = path_arg("page_url", args)?;
+        Ok(tera::Value::String(ctx.page_url(path)))
+    });
+}
+
+/// Get the required `path` string argument of a template function.
+fn path_arg<'a>(name: &str, args: &'a HashMap<String, tera::Value>) -> tera::Result<&'a str> {
+    args.get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| tera::Error::msg(format!("{} requires a `path` string argument", name)))
 }
 
 /// Slugify filter: converts "Hello World" to "hello-world".
@@ -165,7 +325,7 @@ mod tests {
 
     #[test]
     fn test_init_templates() {
-        let tera = init_templates().expect("Failed to initialize templates");
+        let tera = init_templates(None).expect("Failed to initialize templates");
         assert!(tera.get_template_names().any(|n| n == "layout.html"));
         assert!(tera.get_template_names().any(|n| n == "docs/page.html"));
         assert!(tera.get_template_names().any(|n| n == "docs/fragment.html"));
@@ -173,7 +333,7 @@ mod tests {
 
     #[test]
     fn test_auth_templates_loaded() {
-        let tera = init_templates().expect("Failed to initialize templates");
+        let tera = init_templates(None).expect("Failed to initialize templates");
         assert!(tera
             .get_template_names()
             .any(|n| n == "partials/signin.html"));
@@ -184,9 +344,79 @@ mod tests {
         assert!(tera.get_template_names().any(|n| n == "403.html"));
     }
 
+    #[test]
+    fn test_theme_overrides_builtin() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("partials")).unwrap();
+        fs::write(
+            dir.path().join("partials/breadcrumb.html"),
+            "<ol class=\"brand-crumbs\"></ol>",
+        )
+        .unwrap();
+        fs::write(dir.path().join("landing.html"), "<main>landing</main>").unwrap();
+        fs::write(dir.path().join("styles.css"), "body {}").unwrap();
+
+        let tera = init_templates(Some(dir.path())).expect("Failed to initialize templates");
+
+        let html = tera
+            .render("partials/breadcrumb.html", &tera::Context::new())
+            .unwrap();
+        assert!(html.contains("brand-crumbs"));
+        assert!(tera.get_template_names().any(|n| n == "landing.html"));
+        assert!(!tera.get_template_names().any(|n| n == "styles.css"));
+        // Builtins without an override are still available
+        assert!(tera.get_template_names().any(|n| n == "layout.html"));
+    }
+
+    #[test]
+    fn test_url_functions() {
+        let mut assets = AssetManifest::new();
+        assets.assets.insert(
+            "theme.css".to_string(),
+            crate::assets::AssetEntry {
+                path: "assets/theme.1234abcd.css".into(),
+                url: "/docs/assets/theme.1234abcd.css".to_string(),
+                integrity: Some("sha384-abc".to_string()),
+            },
+        );
+
+        let mut tera = init_templates(None).unwrap();
+        register_functions(
+            &mut tera,
+            UrlContext {
+                assets,
+                base_url: "/docs/".to_string(),
+            },
+        );
+
+        let html = tera
+            .render_str(
+                r#"{{ asset_url(path="theme.css") }}|{{ asset_integrity(path="theme.css") }}|{{ page_url(path="guide/install.md") }}|{{ page_url(path="guide/README.md") }}"#,
+                &tera::Context::new(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            html,
+            "/docs/assets/theme.1234abcd.css|sha384-abc|/docs/guide/install|/docs/guide/"
+        );
+        assert!(tera
+            .render_str(
+                r#"{{ asset_url(path="missing.css") }}"#,
+                &tera::Context::new()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_missing_theme_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(init_templates(Some(&dir.path().join("missing"))).is_err());
+    }
+
     #[test]
     fn test_search_template_loaded() {
-        let tera = init_templates().expect("Failed to initialize templates");
+        let tera = init_templates(None).expect("Failed to initialize templates");
         assert!(tera
             .get_template_names()
             .any(|n| n == "partials/search.html"));